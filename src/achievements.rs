@@ -0,0 +1,80 @@
+use crate::{
+    persist::{load_toml, save_toml},
+    progress::RunProgress,
+};
+use dungeon_raid_core::game::Game;
+use serde::{Deserialize, Serialize};
+
+const ACHIEVEMENTS_FILE: &'static str = "achievements.toml";
+
+pub struct Achievement {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub description: &'static str,
+    is_unlocked: fn(&RunProgress, &Game) -> bool,
+}
+
+// add new achievements here; `id` is what gets persisted, so never change an existing one
+pub const ACHIEVEMENTS: &[Achievement] = &[
+    Achievement {
+        id: "chain_8",
+        name: "Chain Gang",
+        description: "chain 8 tiles in a single turn",
+        is_unlocked: |progress, _game| progress.longest_chain >= 8,
+    },
+    Achievement {
+        id: "chain_15",
+        name: "Chain Reaction",
+        description: "chain 15 tiles in a single turn",
+        is_unlocked: |progress, _game| progress.longest_chain >= 15,
+    },
+    Achievement {
+        id: "survive_50",
+        name: "Survivor",
+        description: "survive 50 turns",
+        is_unlocked: |_progress, game| game.turn() as u64 >= 50,
+    },
+    Achievement {
+        id: "survive_200",
+        name: "Veteran",
+        description: "survive 200 turns",
+        is_unlocked: |_progress, game| game.turn() as u64 >= 200,
+    },
+    Achievement {
+        id: "coins_100",
+        name: "Hoarder",
+        description: "reach 100 coins",
+        // counted over the run, the purse empties on every coin purchase
+        is_unlocked: |_progress, game| game.stats().coins_collected as u64 >= 100,
+    },
+];
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct UnlockedAchievements {
+    unlocked: Vec<String>,
+}
+
+impl UnlockedAchievements {
+    pub fn load() -> Self {
+        load_toml(ACHIEVEMENTS_FILE)
+    }
+
+    pub fn is_unlocked(&self, achievement: &Achievement) -> bool {
+        self.unlocked.iter().any(|id| id == achievement.id)
+    }
+
+    /// unlocks and persists any achievements newly satisfied, returning them
+    pub fn evaluate(&mut self, progress: &RunProgress, game: &Game) -> Vec<&'static Achievement> {
+        let newly_unlocked: Vec<&'static Achievement> = ACHIEVEMENTS
+            .iter()
+            .filter(|a| !self.is_unlocked(a) && (a.is_unlocked)(progress, game))
+            .collect();
+        if !newly_unlocked.is_empty() {
+            for a in newly_unlocked.iter() {
+                self.unlocked.push(String::from(a.id));
+            }
+            save_toml(ACHIEVEMENTS_FILE, self);
+        }
+        newly_unlocked
+    }
+}
@@ -0,0 +1,61 @@
+use dungeon_raid_core::game::tile::{TilePosition, TileType};
+
+/// A command typed into the `:` prompt.
+pub enum Command {
+    /// move the cursor onto a tile
+    Goto(TilePosition),
+    /// debug only: replace a tile with a fresh one of the given type
+    SetTile(TilePosition, TileType),
+}
+
+impl Command {
+    pub fn parse(input: &str, debug: bool) -> Result<Self, String> {
+        let mut words = input.split_whitespace();
+        let name = words.next().unwrap_or_default();
+        let args: Vec<&str> = words.collect();
+        match name {
+            "goto" => {
+                let [row, col] = args[..] else {
+                    return Err(String::from("usage: goto <row> <col>"));
+                };
+                Ok(Command::Goto(parse_position(row, col)?))
+            }
+            "settile" if debug => {
+                let [row, col, tile_type] = args[..] else {
+                    return Err(String::from("usage: settile <row> <col> <type>"));
+                };
+                Ok(Command::SetTile(
+                    parse_position(row, col)?,
+                    parse_tile_type(tile_type)?,
+                ))
+            }
+            "" => Err(String::from("no command given")),
+            _ => Err(format!("unknown command '{}'", name)),
+        }
+    }
+}
+
+fn parse_position(row: &str, col: &str) -> Result<TilePosition, String> {
+    let y = row
+        .parse::<isize>()
+        .map_err(|_| format!("'{}' is not a row", row))?;
+    let x = col
+        .parse::<isize>()
+        .map_err(|_| format!("'{}' is not a column", col))?;
+    Ok(TilePosition::new(y, x))
+}
+
+fn parse_tile_type(name: &str) -> Result<TileType, String> {
+    match name.to_lowercase().as_str() {
+        "potion" => Ok(TileType::Potion),
+        "shield" => Ok(TileType::Shield),
+        "coin" => Ok(TileType::Coin),
+        "sword" => Ok(TileType::Sword),
+        "enemy" => Ok(TileType::Enemy),
+        "special" => Ok(TileType::Special),
+        _ => Err(format!(
+            "unknown tile type '{}', expected potion|shield|coin|sword|enemy|special",
+            name
+        )),
+    }
+}
@@ -0,0 +1,385 @@
+use crossterm::event::KeyCode;
+use std::collections::BTreeMap;
+
+const KEYBINDINGS_FILE: &'static str = "keys.toml";
+
+/// Something a remappable key does.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Action {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    Select,
+    Drop,
+    CastAbility1,
+    CastAbility2,
+    CastAbility3,
+    CastAbility4,
+    Quit,
+    // improvement screen, and the loadout and ability lists
+    ToggleChoice,
+    ConfirmChoices,
+    // backs out of a list or a targeted cast
+    Cancel,
+}
+
+// actions whose keys are checked against each other, one list per screen
+const BOARD_ACTIONS: &[Action] = &[
+    Action::MoveUp,
+    Action::MoveDown,
+    Action::MoveLeft,
+    Action::MoveRight,
+    Action::Select,
+    Action::Drop,
+    Action::CastAbility1,
+    Action::CastAbility2,
+    Action::CastAbility3,
+    Action::CastAbility4,
+    Action::Quit,
+];
+const IMPROVEMENT_ACTIONS: &[Action] = &[
+    Action::MoveUp,
+    Action::MoveDown,
+    Action::ToggleChoice,
+    Action::ConfirmChoices,
+    Action::Cancel,
+    Action::Quit,
+];
+// the starting loadout, the ability manager and the menus
+const LIST_ACTIONS: &[Action] = &[
+    Action::MoveUp,
+    Action::MoveDown,
+    Action::ToggleChoice,
+    Action::ConfirmChoices,
+    Action::Cancel,
+    Action::Quit,
+];
+const TARGETING_ACTIONS: &[Action] = &[
+    Action::MoveUp,
+    Action::MoveDown,
+    Action::MoveLeft,
+    Action::MoveRight,
+    Action::Select,
+    Action::ConfirmChoices,
+    Action::Cancel,
+    Action::Quit,
+];
+
+// keys with a fixed meaning on each screen, which a binding would shadow or be shadowed by
+const FIXED_BOARD_KEYS: &[KeyCode] = &[
+    KeyCode::Char('a'),
+    KeyCode::Char('s'),
+    KeyCode::Char('p'),
+    KeyCode::Char('L'),
+    KeyCode::Char('P'),
+    KeyCode::Char('i'),
+    KeyCode::Char('A'),
+    KeyCode::Char('H'),
+    KeyCode::Char('z'),
+    KeyCode::Char('F'),
+    KeyCode::Char('G'),
+    KeyCode::Char('R'),
+    KeyCode::Char('C'),
+    KeyCode::Char('e'),
+    KeyCode::Char('.'),
+    KeyCode::Char('g'),
+    KeyCode::Char('n'),
+    KeyCode::Char('u'),
+    KeyCode::Char(':'),
+    KeyCode::Char('?'),
+    KeyCode::Backspace,
+    KeyCode::Esc,
+];
+const FIXED_IMPROVEMENT_KEYS: &[KeyCode] = &[
+    KeyCode::Char('p'),
+    KeyCode::Char('1'),
+    KeyCode::Char('2'),
+    KeyCode::Char('3'),
+    KeyCode::Char('4'),
+    KeyCode::Char('5'),
+    KeyCode::Char('6'),
+    KeyCode::Char('7'),
+    KeyCode::Char('8'),
+    KeyCode::Char('9'),
+    KeyCode::Char('?'),
+];
+const FIXED_LIST_KEYS: &[KeyCode] = &[
+    KeyCode::Char('r'),
+    KeyCode::Char('A'),
+    KeyCode::Char('J'),
+    KeyCode::Char('K'),
+    KeyCode::Char('d'),
+    KeyCode::Char('a'),
+    KeyCode::Char('L'),
+    KeyCode::Char('P'),
+    KeyCode::Char('i'),
+    KeyCode::Char('?'),
+];
+
+impl Action {
+    const ALL: [Action; 14] = [
+        Action::MoveUp,
+        Action::MoveDown,
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::Select,
+        Action::Drop,
+        Action::CastAbility1,
+        Action::CastAbility2,
+        Action::CastAbility3,
+        Action::CastAbility4,
+        Action::Quit,
+        Action::ToggleChoice,
+        Action::ConfirmChoices,
+        Action::Cancel,
+    ];
+
+    /// the name used in keys.toml
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::MoveUp => "move_up",
+            Action::MoveDown => "move_down",
+            Action::MoveLeft => "move_left",
+            Action::MoveRight => "move_right",
+            Action::Select => "select",
+            Action::Drop => "drop",
+            Action::CastAbility1 => "cast_ability_1",
+            Action::CastAbility2 => "cast_ability_2",
+            Action::CastAbility3 => "cast_ability_3",
+            Action::CastAbility4 => "cast_ability_4",
+            Action::Quit => "quit",
+            Action::ToggleChoice => "toggle_choice",
+            Action::ConfirmChoices => "confirm_choices",
+            Action::Cancel => "cancel",
+        }
+    }
+
+    fn default_keys(&self) -> Vec<KeyCode> {
+        match self {
+            Action::MoveUp => vec![KeyCode::Char('k'), KeyCode::Up],
+            Action::MoveDown => vec![KeyCode::Char('j'), KeyCode::Down],
+            Action::MoveLeft => vec![KeyCode::Char('h'), KeyCode::Left],
+            Action::MoveRight => vec![KeyCode::Char('l'), KeyCode::Right],
+            Action::Select => vec![KeyCode::Char('x')],
+            Action::Drop => vec![KeyCode::Char(' ')],
+            Action::CastAbility1 => vec![KeyCode::Char('1')],
+            Action::CastAbility2 => vec![KeyCode::Char('2')],
+            Action::CastAbility3 => vec![KeyCode::Char('3')],
+            Action::CastAbility4 => vec![KeyCode::Char('4')],
+            Action::Quit => vec![KeyCode::Char('q')],
+            Action::ToggleChoice => vec![KeyCode::Char(' ')],
+            Action::ConfirmChoices => vec![KeyCode::Enter],
+            Action::Cancel => vec![KeyCode::Esc],
+        }
+    }
+}
+
+/// Keys for each remappable action, read from keys.toml at startup, e.g.
+///
+/// ```toml
+/// move_up = ["w", "Up"]
+/// move_left = ["a", "Left"]
+/// ```
+///
+/// An action left out keeps its default keys. The fixed commands, like `a` for
+/// achievements, can't be rebound over; keys.toml is rejected at startup if it tries.
+#[derive(Clone)]
+pub struct Keybindings {
+    keys: Vec<(Action, Vec<KeyCode>)>,
+}
+
+impl Default for Keybindings {
+    fn default() -> Self {
+        Self {
+            keys: Action::ALL
+                .iter()
+                .map(|action| (*action, action.default_keys()))
+                .collect(),
+        }
+    }
+}
+
+impl Keybindings {
+    /// unlike the other config files a bad keys.toml is an error, so a typo can't lock you out
+    pub fn load() -> Result<Self, String> {
+        let contents = match std::fs::read_to_string(KEYBINDINGS_FILE) {
+            Ok(contents) => contents,
+            Err(_) => return Ok(Self::default()),
+        };
+        let table: BTreeMap<String, Vec<String>> = toml::from_str(&contents)
+            .map_err(|e| format!("failed to parse {}: {}", KEYBINDINGS_FILE, e))?;
+        Self::from_table(KEYBINDINGS_FILE, table)
+    }
+
+    /// key names by action name, spelled as in keys.toml; `source` is named in errors
+    pub fn from_table(source: &str, table: BTreeMap<String, Vec<String>>) -> Result<Self, String> {
+        let mut keybindings = Self::default();
+        for (name, key_names) in table {
+            let action = Action::ALL
+                .into_iter()
+                .find(|action| action.name() == name)
+                .ok_or(format!("{}: unknown action '{}'", source, name))?;
+            let keys = key_names
+                .iter()
+                .map(|key| {
+                    key_from_name(key)
+                        .ok_or(format!("{}: unknown key '{}' for {}", source, key, name))
+                })
+                .collect::<Result<Vec<KeyCode>, String>>()?;
+            if let Some((_, bound)) = keybindings.keys.iter_mut().find(|(a, _)| *a == action) {
+                *bound = keys;
+            }
+        }
+        keybindings.check_conflicts(source, BOARD_ACTIONS, FIXED_BOARD_KEYS, "the board")?;
+        keybindings.check_conflicts(
+            source,
+            IMPROVEMENT_ACTIONS,
+            FIXED_IMPROVEMENT_KEYS,
+            "the improvement screen",
+        )?;
+        keybindings.check_conflicts(source, LIST_ACTIONS, FIXED_LIST_KEYS, "the lists")?;
+        keybindings.check_conflicts(source, TARGETING_ACTIONS, &[], "ability targeting")?;
+        Ok(keybindings)
+    }
+
+    fn check_conflicts(
+        &self,
+        source: &str,
+        actions: &[Action],
+        fixed_keys: &[KeyCode],
+        screen: &str,
+    ) -> Result<(), String> {
+        for action in actions.iter() {
+            if let Some(key) = self.keys(*action).iter().find(|k| fixed_keys.contains(*k)) {
+                return Err(format!(
+                    "{}: '{}' is bound to {} but already has a fixed use on {}",
+                    source,
+                    key_name(*key).unwrap_or_default(),
+                    action.name(),
+                    screen
+                ));
+            }
+        }
+        for (idx, action) in actions.iter().enumerate() {
+            for other in actions[idx + 1..].iter() {
+                if let Some(key) = self.keys(*action).iter().find(|k| self.is(**k, *other)) {
+                    return Err(format!(
+                        "{}: '{}' is bound to both {} and {}",
+                        source,
+                        key_name(*key).unwrap_or_default(),
+                        action.name(),
+                        other.name()
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// every action with its keys, e.g. for writing them back out
+    pub fn bindings(&self) -> &[(Action, Vec<KeyCode>)] {
+        &self.keys
+    }
+
+    pub fn keys(&self, action: Action) -> &[KeyCode] {
+        self.keys
+            .iter()
+            .find(|(a, _)| *a == action)
+            .map(|(_, keys)| &keys[..])
+            .unwrap_or_default()
+    }
+
+    pub fn is(&self, code: KeyCode, action: Action) -> bool {
+        self.keys(action).contains(&code)
+    }
+
+    pub fn board_action(&self, code: KeyCode) -> Option<Action> {
+        BOARD_ACTIONS.iter().copied().find(|a| self.is(code, *a))
+    }
+
+    pub fn improvement_action(&self, code: KeyCode) -> Option<Action> {
+        IMPROVEMENT_ACTIONS
+            .iter()
+            .copied()
+            .find(|a| self.is(code, *a))
+    }
+
+    pub fn list_action(&self, code: KeyCode) -> Option<Action> {
+        LIST_ACTIONS.iter().copied().find(|a| self.is(code, *a))
+    }
+
+    pub fn targeting_action(&self, code: KeyCode) -> Option<Action> {
+        TARGETING_ACTIONS
+            .iter()
+            .copied()
+            .find(|a| self.is(code, *a))
+    }
+
+    /// "k / Up", for the help overlay
+    pub fn describe(&self, action: Action) -> String {
+        let names: Vec<String> = self
+            .keys(action)
+            .iter()
+            .filter_map(|key| key_name(*key))
+            .collect();
+        names.join(" / ")
+    }
+}
+
+/// the keys the game reacts to, written the way keys.toml and replays spell them; None for
+/// media and lone modifier keys, which nothing uses
+pub fn key_name(key: KeyCode) -> Option<String> {
+    let name = match key {
+        KeyCode::Char(' ') => String::from("Space"),
+        KeyCode::Char(c) => c.to_string(),
+        KeyCode::Enter => String::from("Enter"),
+        KeyCode::Esc => String::from("Esc"),
+        KeyCode::Backspace => String::from("Backspace"),
+        KeyCode::Up => String::from("Up"),
+        KeyCode::Down => String::from("Down"),
+        KeyCode::Left => String::from("Left"),
+        KeyCode::Right => String::from("Right"),
+        KeyCode::Tab => String::from("Tab"),
+        KeyCode::BackTab => String::from("BackTab"),
+        KeyCode::Home => String::from("Home"),
+        KeyCode::End => String::from("End"),
+        KeyCode::PageUp => String::from("PageUp"),
+        KeyCode::PageDown => String::from("PageDown"),
+        KeyCode::Delete => String::from("Delete"),
+        KeyCode::Insert => String::from("Insert"),
+        KeyCode::F(n) => format!("F{}", n),
+        _ => return None,
+    };
+    Some(name)
+}
+
+pub fn key_from_name(name: &str) -> Option<KeyCode> {
+    let key = match name {
+        "Space" => KeyCode::Char(' '),
+        "Enter" => KeyCode::Enter,
+        "Esc" => KeyCode::Esc,
+        "Backspace" => KeyCode::Backspace,
+        "Up" => KeyCode::Up,
+        "Down" => KeyCode::Down,
+        "Left" => KeyCode::Left,
+        "Right" => KeyCode::Right,
+        "Tab" => KeyCode::Tab,
+        "BackTab" => KeyCode::BackTab,
+        "Home" => KeyCode::Home,
+        "End" => KeyCode::End,
+        "PageUp" => KeyCode::PageUp,
+        "PageDown" => KeyCode::PageDown,
+        "Delete" => KeyCode::Delete,
+        "Insert" => KeyCode::Insert,
+        _ => {
+            let mut chars = name.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => KeyCode::Char(c),
+                (Some('F'), Some(_)) => KeyCode::F(name[1..].parse().ok()?),
+                _ => return None,
+            }
+        }
+    };
+    Some(key)
+}
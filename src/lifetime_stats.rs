@@ -0,0 +1,52 @@
+use crate::persist::{load_toml, save_toml};
+use dungeon_raid_core::game::Game;
+use serde::{Deserialize, Serialize};
+
+const LIFETIME_STATS_FILE: &'static str = "stats.toml";
+
+/// Totals across every finished run, updated on game over.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LifetimeStats {
+    pub games_played: u64,
+    pub total_turns: u64,
+    pub total_enemies_slain: u64,
+    pub total_coins: u64,
+    pub longest_run: u64,
+    pub best_score: u64,
+}
+
+impl LifetimeStats {
+    pub fn load() -> Self {
+        load_toml(LIFETIME_STATS_FILE)
+    }
+
+    /// adds a finished run, taking its totals from the core's own counts
+    pub fn record_run(&mut self, game: &Game) {
+        let stats = game.stats();
+        let turns = game.turn() as u64;
+        let score = game.score();
+        self.games_played += 1;
+        self.total_turns += turns;
+        self.total_enemies_slain += stats.enemies_slain as u64;
+        self.total_coins += stats.coins_collected as u64;
+        if turns > self.longest_run {
+            self.longest_run = turns;
+        }
+        if score > self.best_score {
+            self.best_score = score;
+        }
+        save_toml(LIFETIME_STATS_FILE, self);
+    }
+
+    pub fn display_lines(&self) -> Vec<String> {
+        vec![
+            format!("games played: {}", self.games_played),
+            format!("turns played: {}", self.total_turns),
+            format!("enemies slain: {}", self.total_enemies_slain),
+            format!("coins collected: {}", self.total_coins),
+            format!("longest run: {} turns", self.longest_run),
+            format!("best score: {}", self.best_score),
+        ]
+    }
+}
@@ -0,0 +1,99 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    io::prelude::*,
+    sync::atomic::{AtomicBool, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const LOG_FILE: &'static str = "log.txt";
+
+#[derive(Copy, Clone, Default, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    // --debug output like the per-turn breakdowns
+    #[default]
+    Debug,
+    Info,
+}
+
+impl Level {
+    fn name(&self) -> &'static str {
+        match self {
+            Level::Debug => "DEBUG",
+            Level::Info => "INFO",
+        }
+    }
+}
+
+/// What happens to log.txt at startup and what gets written to it.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LogSettings {
+    // move the last runs' logs to log.1.txt, log.2.txt, ... instead of clearing log.txt
+    pub rotate: bool,
+    // how many old logs rotation keeps
+    pub keep: usize,
+    // entries below this level are dropped
+    pub level: Level,
+}
+
+impl Default for LogSettings {
+    fn default() -> Self {
+        Self {
+            rotate: false,
+            keep: 3,
+            level: Level::Debug,
+        }
+    }
+}
+
+static DEBUG_ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// clears or rotates log.txt; call once at startup before anything is logged
+pub fn init(settings: &LogSettings) {
+    DEBUG_ENABLED.store(settings.level <= Level::Debug, Ordering::Relaxed);
+    if settings.rotate && settings.keep > 0 {
+        // a missing file just means there's nothing to shift yet
+        let _ = std::fs::remove_file(rotated_path(settings.keep));
+        for n in (1..settings.keep).rev() {
+            let _ = std::fs::rename(rotated_path(n), rotated_path(n + 1));
+        }
+        let _ = std::fs::rename(LOG_FILE, rotated_path(1));
+    }
+    std::fs::File::create(LOG_FILE).expect("failed to create file");
+}
+
+fn rotated_path(n: usize) -> String {
+    format!("log.{}.txt", n)
+}
+
+pub fn debug(msg: &str) {
+    if DEBUG_ENABLED.load(Ordering::Relaxed) {
+        write_entry(Level::Debug, msg);
+    }
+}
+
+pub fn info(msg: &str) {
+    write_entry(Level::Info, msg);
+}
+
+// "[1760620000.123] INFO failed to save save.toml: ...", seconds since the unix epoch
+fn write_entry(level: Level, msg: &str) {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let mut file = std::fs::File::options()
+        .append(true)
+        .create(true)
+        .open(LOG_FILE)
+        .expect("failed to create file");
+    writeln!(
+        &mut file,
+        "[{}.{:03}] {} {}",
+        timestamp.as_secs(),
+        timestamp.subsec_millis(),
+        level.name(),
+        msg
+    )
+    .expect("failed to write file");
+}
@@ -1,56 +1,339 @@
+mod achievements;
+mod command;
+mod keybindings;
+mod lifetime_stats;
+mod logging;
+mod mode;
+mod persist;
+mod progress;
+mod replay;
+mod seed_bests;
+mod settings;
+mod theme;
+
+use achievements::{UnlockedAchievements, ACHIEVEMENTS};
+use command::Command;
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, MouseButton, MouseEvent,
+        MouseEventKind,
+    },
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use dungeon_raid_core::game::{
+    ability::{AbilityType, CastResult},
     improvement_choices::ImprovementInfo,
+    special::{BossPhase, SpecialType},
     tile::{Tile, TileInfo, TilePosition, TileType, Wind8},
-    Game, DEFAULT_BOARD_HEIGHT, DEFAULT_BOARD_WIDTH,
+    Difficulty, Game, GameSnapshot, GravityDirection, SelectionMode, SuggestedMove,
 };
+use keybindings::{key_name, Action, Keybindings};
+use lifetime_stats::LifetimeStats;
+use mode::GameMode;
+use progress::{RunProgress, StatTotals, TurnSummary};
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
 use ratatui::{
     backend::{Backend, CrosstermBackend},
     buffer::Buffer,
-    layout::Rect,
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
-    widgets::Widget,
+    text::{Span, Spans},
+    widgets::{
+        Block, Borders, Clear, List, ListItem, ListState, Paragraph, Row, StatefulWidget, Table,
+        Widget, Wrap,
+    },
     Frame, Terminal,
 };
-use std::{error::Error, io, io::prelude::*};
+use replay::{Check, Replay};
+use seed_bests::SeedBests;
+use serde::{Deserialize, Serialize};
+use settings::{
+    HudPosition, ImprovementCommit, NumberFormat, PathStyle, SelectionStartStyle, Settings,
+    StatDisplay,
+};
+use std::{
+    error::Error,
+    io,
+    io::prelude::*,
+    time::{Duration, Instant},
+};
+use theme::Theme;
+
+// the run in progress, written with 's' and resumed at startup
+const SAVE_FILE: &'static str = "save.toml";
+
+/// A saved run: the core's game as json, plus the totals only the frontend keeps.
+#[derive(Serialize, Deserialize)]
+struct SavedRun {
+    game: String,
+    // #[serde(default)] on RunProgress lets a save missing some of it still load
+    progress: RunProgress,
+}
+
+fn load_saved_game() -> Option<(Game, RunProgress)> {
+    let contents = std::fs::read_to_string(SAVE_FILE).ok()?;
+    let loaded = toml::from_str::<SavedRun>(&contents)
+        .map_err(|e| e.to_string())
+        .and_then(|saved| {
+            Game::from_json(&saved.game)
+                .map(|game| (game, saved.progress))
+                .map_err(|e| e.to_string())
+        });
+    match loaded {
+        Ok(run) => Some(run),
+        Err(e) => {
+            logging::info(&format!("failed to load {}: {}", SAVE_FILE, e));
+            None
+        }
+    }
+}
+
+const USAGE: &str = "usage: tui-dungeon-raid [options]
+
+options:
+  --mode endless|objective   game mode (default endless)
+  --background light|dark    terminal background, detected when omitted
+  --board WIDTHxHEIGHT       board size in tiles, e.g. 10x10
+  --difficulty easy|normal|hard
+                             enemy strength and how often they spawn (default normal)
+  --gravity down|up|left|right
+                             which way tiles fall (default down)
+  --seed N                   replay the board of a previous run, see the run info
+  --record FILE              write every key pressed to FILE, keyboard only
+  --replay FILE              play a recorded run back with the options and keys it was
+                             recorded with, q stops it
+  --verify-replay FILE       play a recording back without a terminal and check every
+                             turn still comes out as recorded
+  --replay-delay MS          longest pause between replayed keys (default 500)
+  --no-mouse                 leave the mouse to the terminal, e.g. for text selection
+  --debug                    debug commands, step-by-step combat and an in-screen log
+  -h, --help                 print this help";
+
+#[derive(Clone)]
+struct Args {
+    mode: GameMode,
+    background: Background,
+    // (width, height) in tiles, the core's default when not given
+    board: Option<(usize, usize)>,
+    gravity: GravityDirection,
+    difficulty: Difficulty,
+    seed: Option<u64>,
+    record: Option<String>,
+    replay: Option<String>,
+    verify_replay: Option<String>,
+    replay_delay: Duration,
+    no_mouse: bool,
+    debug: bool,
+    // from keys.toml rather than the command line, but fixed for the session all the same
+    keybindings: Keybindings,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = Args {
+        mode: GameMode::Endless,
+        background: Background::detect(),
+        board: None,
+        gravity: GravityDirection::Down,
+        difficulty: Difficulty::Normal,
+        seed: None,
+        record: None,
+        replay: None,
+        verify_replay: None,
+        replay_delay: Duration::from_millis(500),
+        no_mouse: false,
+        debug: false,
+        keybindings: Keybindings::default(),
+    };
+    let mut arg_iter = std::env::args().skip(1);
+    while let Some(arg) = arg_iter.next() {
+        match arg.as_str() {
+            "--mode" => {
+                let value = arg_iter.next().unwrap_or_default();
+                args.mode = GameMode::from_arg(&value).ok_or(format!(
+                    "unknown mode '{}', expected endless|objective",
+                    value
+                ))?;
+            }
+            "--background" => {
+                let value = arg_iter.next().unwrap_or_default();
+                args.background = Background::from_arg(&value).ok_or(format!(
+                    "unknown background '{}', expected light|dark",
+                    value
+                ))?;
+            }
+            "--board" => {
+                let value = arg_iter.next().unwrap_or_default();
+                let dimensions = value
+                    .split_once('x')
+                    .and_then(|(w, h)| Some((w.parse::<usize>().ok()?, h.parse::<usize>().ok()?)))
+                    .filter(|(w, h)| *w > 0 && *h > 0);
+                args.board = Some(dimensions.ok_or(format!(
+                    "bad board size '{}', expected WIDTHxHEIGHT like 8x8",
+                    value
+                ))?);
+            }
+            "--gravity" => {
+                let value = arg_iter.next().unwrap_or_default();
+                args.gravity = gravity_from_name(&value).ok_or(format!(
+                    "unknown gravity '{}', expected down|up|left|right",
+                    value
+                ))?;
+            }
+            "--difficulty" => {
+                let value = arg_iter.next().unwrap_or_default();
+                args.difficulty = difficulty_from_name(&value).ok_or(format!(
+                    "unknown difficulty '{}', expected easy|normal|hard",
+                    value
+                ))?;
+            }
+            "--seed" => {
+                let value = arg_iter.next().unwrap_or_default();
+                args.seed = Some(
+                    value
+                        .parse::<u64>()
+                        .map_err(|_| format!("bad seed '{}', expected a number", value))?,
+                );
+            }
+            "--record" => args.record = Some(arg_iter.next().unwrap_or_default()),
+            "--replay" => args.replay = Some(arg_iter.next().unwrap_or_default()),
+            "--verify-replay" => args.verify_replay = Some(arg_iter.next().unwrap_or_default()),
+            "--replay-delay" => {
+                let value = arg_iter.next().unwrap_or_default();
+                args.replay_delay =
+                    Duration::from_millis(value.parse::<u64>().map_err(|_| {
+                        format!("bad replay delay '{}', expected milliseconds", value)
+                    })?);
+            }
+            "--no-mouse" => args.no_mouse = true,
+            "--debug" => args.debug = true,
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                std::process::exit(0);
+            }
+            _ => return Err(format!("unknown argument '{}', see --help", arg)),
+        }
+    }
+    // the core only seeds its default sized board
+    if args.seed.is_some() && args.board.is_some() {
+        return Err(String::from("--seed can't be combined with --board"));
+    }
+    // a recording only knows its seed
+    if (args.record.is_some() || args.replay.is_some()) && args.board.is_some() {
+        return Err(String::from(
+            "--record and --replay can't be combined with --board",
+        ));
+    }
+    if args.record.is_some() && args.replay.is_some() {
+        return Err(String::from("--record can't be combined with --replay"));
+    }
+    if args.verify_replay.is_some()
+        && (args.record.is_some() || args.replay.is_some() || args.board.is_some())
+    {
+        return Err(String::from(
+            "--verify-replay can't be combined with --record, --replay or --board",
+        ));
+    }
+    args.keybindings = Keybindings::load()?;
+    Ok(args)
+}
 
-const LOG_FILE: &'static str = "log.txt";
-fn clear_log_file() {
-    let mut file = std::fs::File::create(LOG_FILE).expect("failed to create file");
-    write!(&mut file, "").expect("failed to write file");
+fn gravity_name(gravity: GravityDirection) -> &'static str {
+    match gravity {
+        GravityDirection::Down => "down",
+        GravityDirection::Up => "up",
+        GravityDirection::Left => "left",
+        GravityDirection::Right => "right",
+    }
+}
+
+fn gravity_from_name(name: &str) -> Option<GravityDirection> {
+    match name {
+        "down" => Some(GravityDirection::Down),
+        "up" => Some(GravityDirection::Up),
+        "left" => Some(GravityDirection::Left),
+        "right" => Some(GravityDirection::Right),
+        _ => None,
+    }
+}
+
+/// marks abilities that work while equipped instead of being cast, in the ability lists
+fn passive_tag(ability_type: AbilityType) -> &'static str {
+    if ability_type.is_passive() {
+        " [passive]"
+    } else {
+        ""
+    }
+}
+
+fn difficulty_name(difficulty: Difficulty) -> &'static str {
+    match difficulty {
+        Difficulty::Easy => "easy",
+        Difficulty::Normal => "normal",
+        Difficulty::Hard => "hard",
+    }
 }
-fn log_to_file(msg: &String) {
-    let mut file = std::fs::File::options()
-        .append(true)
-        .create(true)
-        .open(LOG_FILE)
-        .expect("failed to create file");
-    writeln!(&mut file, "{}", msg).expect("failed to write file");
+
+fn difficulty_from_name(name: &str) -> Option<Difficulty> {
+    match name {
+        "easy" => Some(Difficulty::Easy),
+        "normal" => Some(Difficulty::Normal),
+        "hard" => Some(Difficulty::Hard),
+        _ => None,
+    }
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
-    clear_log_file();
+    let args = parse_args()?;
+    // needs no terminal, so it can run from tests
+    if let Some(ref path) = args.verify_replay {
+        logging::init(&Settings::load().log);
+        let replay = Replay::load(path)?;
+        // one recorded before checks were written would pass without testing anything
+        if replay.checks.is_empty() {
+            return Err(format!("{}: no checks to verify, record it again", path).into());
+        }
+        let checked = verify_replay(args.clone(), &replay)
+            .map_err(|turn| format!("{}: diverged from the recording at turn {}", path, turn))?;
+        println!("{}: all {} checks match", path, checked);
+        return Ok(());
+    }
+    let replay = match args.replay {
+        Some(ref path) => Some(Replay::load(path)?),
+        None => None,
+    };
+    logging::init(&Settings::load().log);
     // setup terminal
     enable_raw_mode()?;
     let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+    execute!(stdout, EnterAlternateScreen)?;
+    // the mouse is optional, so a failure here only disables it
+    // recordings are keyboard only, so the mouse stays off while one is made or played
+    let mouse_captured = !args.no_mouse
+        && args.record.is_none()
+        && replay.is_none()
+        && match execute!(stdout, EnableMouseCapture) {
+            Ok(()) => true,
+            Err(err) => {
+                logging::info(&format!("failed to enable mouse capture: {err}"));
+                false
+            }
+        };
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
     // create app and run it
-    let res = run_app(&mut terminal);
+    let res = match replay {
+        Some(replay) => replay_app(&mut terminal, args, replay),
+        None => run_app(&mut terminal, args, mouse_captured),
+    };
 
     // restore terminal
     disable_raw_mode()?;
-    execute!(
-        terminal.backend_mut(),
-        LeaveAlternateScreen,
-        DisableMouseCapture
-    )?;
+    if mouse_captured {
+        execute!(terminal.backend_mut(), DisableMouseCapture)?;
+    }
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
     terminal.show_cursor()?;
 
     if let Err(err) = res {
@@ -64,6 +347,103 @@ fn main() -> Result<(), Box<dyn Error>> {
 enum GameState {
     Playing,
     ChoosingImprovement(usize), //num_choices
+    Victory,
+    // died or failed the objective; only restart or quit from here
+    GameOver,
+    // managing the ability loadout, opened from play
+    Abilities,
+    // picking starting abilities before the run begins
+    ChoosingLoadout,
+    // picking the tile a cast ability acts on, by slot
+    TargetingAbility(usize),
+    // over play or the improvement screen, which come back as they were on resume
+    Paused,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum Menu {
+    Achievements,
+    LifetimeStats,
+    Specials,
+    RunStats,
+}
+impl Menu {
+    fn toggle_key(&self) -> KeyCode {
+        match self {
+            Menu::Achievements => KeyCode::Char('a'),
+            Menu::LifetimeStats => KeyCode::Char('L'),
+            Menu::Specials => KeyCode::Char('P'),
+            Menu::RunStats => KeyCode::Char('i'),
+        }
+    }
+}
+
+const TICK_RATE: Duration = Duration::from_millis(250);
+// time between rows while tiles fall, when animate_gravity is on
+const GRAVITY_FRAME: Duration = Duration::from_millis(60);
+const STATUS_MESSAGE_DURATION: Duration = Duration::from_secs(3);
+// time spent on mouse-driven screens without a single mouse event before
+// suggesting the terminal isn't delivering them
+const MOUSE_HINT_AFTER: Duration = Duration::from_secs(30);
+const MOUSE_HINT: &str = "mouse may be unsupported here, keyboard controls still work";
+
+struct StatusMessage {
+    text: String,
+    expires_at: Instant,
+}
+impl StatusMessage {
+    fn new(text: String) -> Self {
+        Self {
+            text,
+            expires_at: Instant::now() + STATUS_MESSAGE_DURATION,
+        }
+    }
+}
+
+/// How loudly an event is announced: danger rings the bell and flashes red,
+/// warning flashes, info only shows and logs.
+#[derive(Copy, Clone, PartialEq)]
+enum Severity {
+    Info,
+    Warning,
+    Danger,
+}
+
+// turn summary toasts kept on screen at once when turns come quickly
+const MAX_TOASTS: usize = 3;
+
+const FLASH_DURATION: Duration = Duration::from_millis(200);
+
+struct Flash {
+    color: Color,
+    started_at: Instant,
+}
+
+/// tints the whole screen for a flash
+struct Tint(Color);
+impl Widget for Tint {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        buf.set_style(area, Style::default().bg(self.0));
+    }
+}
+
+const SHIELD_DRAIN_DURATION: Duration = Duration::from_secs(1);
+const SHIELD_GAUGE_WIDTH: u64 = 10;
+
+/// animates the shield gauge down from `from` to the current shields after they absorb a hit
+struct ShieldDrain {
+    from: u64,
+    started_at: Instant,
+}
+impl ShieldDrain {
+    fn displayed_shields(&self, current: u64) -> Option<u64> {
+        let elapsed = self.started_at.elapsed();
+        if elapsed >= SHIELD_DRAIN_DURATION || self.from <= current {
+            return None;
+        }
+        let remaining = 1.0 - elapsed.as_secs_f64() / SHIELD_DRAIN_DURATION.as_secs_f64();
+        Some(current + ((self.from - current) as f64 * remaining).round() as u64)
+    }
 }
 
 #[derive(Copy, Clone)]
@@ -74,86 +454,225 @@ enum CursorMove {
     Left,
 }
 
+// rows from one tile to the next, the blot and the arrow row below it; columns go by the
+// board's stride
 const PLAYING_CURSOR_MOVE: u16 = 2;
 const PLAYING_CURSOR_MAX_UP: u16 = 0;
-const PLAYING_CURSOR_MAX_RIGHT: u16 = PLAYING_CURSOR_MAX_LEFT + DEFAULT_BOARD_WIDTH as u16 * 2 - 1;
-const PLAYING_CURSOR_MAX_DOWN: u16 = PLAYING_CURSOR_MAX_UP + DEFAULT_BOARD_HEIGHT as u16 * 2 - 1;
 const PLAYING_CURSOR_MAX_LEFT: u16 = 0;
-const CHOOSING_IMPROVEMENT_CURSOR_MOVE: u16 = 1;
-const CHOOSING_IMPROVEMENT_CURSOR_MAX_UP: u16 = 1;
 
-fn move_cursor<B: Backend>(
-    terminal: &mut Terminal<B>,
-    m: CursorMove,
-    gs: GameState,
-) -> io::Result<(u16, u16)> {
-    let mut cursor_pos = terminal.get_cursor()?;
-    log_to_file(&format!(
-        "move_cursor called with terminal.get_cursor() giving (x {}, y {})",
-        cursor_pos.0, cursor_pos.1
-    ));
-    match gs {
-        GameState::Playing => {
-            match m {
-                CursorMove::Up => {
-                    if cursor_pos.1 >= PLAYING_CURSOR_MAX_UP + PLAYING_CURSOR_MOVE {
-                        cursor_pos.1 -= PLAYING_CURSOR_MOVE;
-                    }
-                }
-                CursorMove::Right => {
-                    if cursor_pos.0 <= PLAYING_CURSOR_MAX_RIGHT - PLAYING_CURSOR_MOVE {
-                        cursor_pos.0 += PLAYING_CURSOR_MOVE;
-                    }
-                }
-                CursorMove::Down => {
-                    if cursor_pos.1 <= PLAYING_CURSOR_MAX_DOWN - PLAYING_CURSOR_MOVE {
-                        cursor_pos.1 += PLAYING_CURSOR_MOVE;
-                    }
-                }
-                CursorMove::Left => {
-                    if cursor_pos.0 >= PLAYING_CURSOR_MAX_LEFT + PLAYING_CURSOR_MOVE {
-                        cursor_pos.0 -= PLAYING_CURSOR_MOVE;
-                    }
-                }
-            };
+/// size of the board in tiles, read from the `Game` rather than assumed, and how wide it's drawn
+#[derive(Copy, Clone)]
+struct BoardSize {
+    width: u16,
+    height: u16,
+    // columns from one tile to the next: the blot, one more for a double-width blot, then the
+    // arrow gap
+    stride: u16,
+}
+impl BoardSize {
+    fn of(game: &Game, wide_glyphs: bool) -> Self {
+        Self {
+            width: game.board_width() as u16,
+            height: game.board_height() as u16,
+            stride: if wide_glyphs { 3 } else { 2 },
         }
-        GameState::ChoosingImprovement(num_choices) => match m {
-            CursorMove::Up => {
-                if cursor_pos.1
-                    >= CHOOSING_IMPROVEMENT_CURSOR_MAX_UP + CHOOSING_IMPROVEMENT_CURSOR_MOVE
-                {
-                    cursor_pos.1 -= CHOOSING_IMPROVEMENT_CURSOR_MOVE;
-                }
+    }
+
+    // columns a blot takes
+    fn blot_width(&self) -> u16 {
+        self.stride - 1
+    }
+
+    /// the column of the arrow gap beside the blot at `blot_x`, toward `dx`
+    fn arrow_column(&self, blot_x: u16, dx: isize) -> u16 {
+        match dx {
+            -1 => blot_x - 1,
+            1 => blot_x + self.blot_width(),
+            _ => blot_x,
+        }
+    }
+
+    // last terminal column/row the board's blots and arrows use
+    fn max_right(&self) -> u16 {
+        PLAYING_CURSOR_MAX_LEFT + self.width * self.stride - 1
+    }
+    fn max_down(&self) -> u16 {
+        PLAYING_CURSOR_MAX_UP + self.height * PLAYING_CURSOR_MOVE - 1
+    }
+
+    fn contains(&self, tp: &TilePosition) -> bool {
+        (0..self.height as isize).contains(&tp.y) && (0..self.width as isize).contains(&tp.x)
+    }
+
+    fn tile_positions(&self) -> Vec<TilePosition> {
+        let mut positions = vec![];
+        for y in 0..self.height {
+            for x in 0..self.width {
+                positions.push(TilePosition::new(y as isize, x as isize));
             }
-            CursorMove::Down => {
-                if cursor_pos.1
-                    <= CHOOSING_IMPROVEMENT_CURSOR_MAX_UP + num_choices as u16
-                        - 1
-                        - CHOOSING_IMPROVEMENT_CURSOR_MOVE
-                {
-                    cursor_pos.1 += CHOOSING_IMPROVEMENT_CURSOR_MOVE;
-                }
+        }
+        positions
+    }
+}
+
+/// the cursor is tracked in `App`, so moving it never has to ask the terminal
+fn move_cursor(mut cursor_pos: (u16, u16), m: CursorMove, board: BoardSize) -> (u16, u16) {
+    match m {
+        CursorMove::Up => {
+            if cursor_pos.1 >= PLAYING_CURSOR_MAX_UP + PLAYING_CURSOR_MOVE {
+                cursor_pos.1 -= PLAYING_CURSOR_MOVE;
             }
-            _ => unreachable!(""),
-        },
+        }
+        CursorMove::Right => {
+            if cursor_pos.0 <= board.max_right() - board.stride {
+                cursor_pos.0 += board.stride;
+            }
+        }
+        CursorMove::Down => {
+            if cursor_pos.1 <= board.max_down() - PLAYING_CURSOR_MOVE {
+                cursor_pos.1 += PLAYING_CURSOR_MOVE;
+            }
+        }
+        CursorMove::Left => {
+            if cursor_pos.0 >= PLAYING_CURSOR_MAX_LEFT + board.stride {
+                cursor_pos.0 -= board.stride;
+            }
+        }
     };
-    Ok(cursor_pos)
+    cursor_pos
+}
+
+fn move_list_selection(state: &mut ListState, m: CursorMove, num_items: usize) {
+    let selected = state.selected().unwrap_or(0);
+    state.select(Some(list_step(selected, m, num_items)));
 }
 
-fn tile_position_from_cursor_position(cursor_position: (u16, u16)) -> TilePosition {
+/// the index one step from `selected`, stopping at either end rather than wrapping
+fn list_step(selected: usize, m: CursorMove, num_items: usize) -> usize {
+    match m {
+        CursorMove::Up => selected.saturating_sub(1),
+        CursorMove::Down if selected + 1 < num_items => selected + 1,
+        CursorMove::Down => selected,
+        _ => unreachable!(""),
+    }
+}
+
+/// the first visible row of a scrolled list, moved only as far as needed to keep
+/// `selected` on screen; matches how `List` scrolls one-line items
+fn scroll_offset_for(selected: usize, offset: usize, height: usize, num_items: usize) -> usize {
+    let offset = offset.min(num_items.saturating_sub(1));
+    if selected < offset {
+        selected
+    } else if height > 0 && selected >= offset + height {
+        selected + 1 - height
+    } else {
+        offset
+    }
+}
+
+fn tile_position_from_cursor_position(
+    cursor_position: (u16, u16),
+    board: BoardSize,
+) -> TilePosition {
     let (x, y) = cursor_position;
     TilePosition::new(
-        ((y - PLAYING_CURSOR_MAX_UP) / 2) as isize,
-        ((x - PLAYING_CURSOR_MAX_LEFT) / 2) as isize,
+        ((y - PLAYING_CURSOR_MAX_UP) / PLAYING_CURSOR_MOVE) as isize,
+        ((x - PLAYING_CURSOR_MAX_LEFT) / board.stride) as isize,
+    )
+}
+
+fn cursor_position_from_tile_position(tp: &TilePosition, board: BoardSize) -> (u16, u16) {
+    (
+        PLAYING_CURSOR_MAX_LEFT + tp.x as u16 * board.stride,
+        PLAYING_CURSOR_MAX_UP + tp.y as u16 * PLAYING_CURSOR_MOVE,
     )
 }
 
-fn improvement_choice_index_from_cursor_position(cursor_position: (u16, u16)) -> usize {
-    let (_x, y) = cursor_position;
-    (y - PLAYING_CURSOR_MAX_UP - 1) as usize
+// two `x` presses on one tile within this window quick-select a chain
+const DOUBLE_PRESS_WINDOW: Duration = Duration::from_millis(400);
+
+// how many debug log lines stay on screen
+const DEBUG_LOG_LINES: usize = 5;
+
+// how many suggested moves are scored for the hint
+const HINT_CANDIDATES: usize = 3;
+
+fn manhattan_distance(a: &TilePosition, b: &TilePosition) -> isize {
+    (a.y - b.y).abs() + (a.x - b.x).abs()
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum JumpTarget {
+    Enemy,
+    // a tile type, named by its blot char
+    Glyph(char),
+}
+impl JumpTarget {
+    fn matches(&self, tile_type: TileType, theme: &Theme) -> bool {
+        match self {
+            JumpTarget::Enemy => matches!(tile_type, TileType::Enemy | TileType::Special),
+            JumpTarget::Glyph(c) => blot_char_from_tile_type(tile_type, theme) == *c,
+        }
+    }
+}
+
+/// remembers where a jump started so repeated presses cycle outward from the same spot
+struct JumpCycle {
+    target: JumpTarget,
+    anchor: TilePosition,
+    index: usize,
+}
+
+/// walks the `next_selection` links from the selection start, giving each selected tile in order
+fn selected_tiles(game: &Game) -> Vec<(TilePosition, TileType)> {
+    let mut selected = vec![];
+    let mut tp_opt = game.get_selection_start();
+    while let Some(tp) = tp_opt {
+        tp_opt = match game.get_tile(&tp) {
+            Some(t) => {
+                selected.push((tp, t.tile_type));
+                match TilePosition::try_from(t.next_selection) {
+                    Ok(relative) => Some(TilePosition::new(tp.y + relative.y, tp.x + relative.x)),
+                    Err(_) => None,
+                }
+            }
+            None => None,
+        };
+    }
+    selected
+}
+
+struct BossStatus {
+    name: &'static str,
+    hit_points: u64,
+    max_hit_points: u64,
+    // below half hit points the core doubles its damage
+    enraged: bool,
+}
+
+fn boss_status(game: &Game) -> Option<BossStatus> {
+    game.specials()
+        .into_iter()
+        .find_map(|(_tp, t, _sid)| match t.tile_info {
+            TileInfo::Special(special) => match special.special_type {
+                SpecialType::Boss => Some(BossStatus {
+                    name: special.special_type.name_description().0,
+                    hit_points: special.being.hit_points as u64,
+                    max_hit_points: special.being.max_hit_points as u64,
+                    enraged: matches!(special.phase, BossPhase::Enraged),
+                }),
+                _ => None,
+            },
+            _ => None,
+        })
 }
 
-fn blot_char_from_tile_type(tile_type: TileType) -> char {
+const BOSS_HP_BAR_WIDTH: u64 = 20;
+
+fn blot_char_from_tile_type(tile_type: TileType, theme: &Theme) -> char {
+    if let Some(blot) = theme.blots.get(tile_type) {
+        return blot;
+    }
     match tile_type {
         TileType::Potion => 'p',
         TileType::Shield => 's',
@@ -165,193 +684,806 @@ fn blot_char_from_tile_type(tile_type: TileType) -> char {
     }
 }
 
-fn bg_fg_color_from_tile_type(tile_type: TileType) -> (Color, Color) {
-    match tile_type {
-        TileType::Potion => (Color::LightMagenta, Color::Black),
-        TileType::Shield => (Color::Blue, Color::Black),
-        TileType::Coin => (Color::Yellow, Color::Black),
-        TileType::Sword => (Color::Green, Color::Black),
-        TileType::Enemy => (Color::Red, Color::Black),
-        TileType::Special => (Color::White, Color::Black),
-        _ => (Color::Black, Color::White),
+#[derive(Copy, Clone, PartialEq)]
+enum Background {
+    Dark,
+    Light,
+}
+impl Background {
+    fn from_arg(arg: &str) -> Option<Self> {
+        match arg {
+            "dark" => Some(Background::Dark),
+            "light" => Some(Background::Light),
+            _ => None,
+        }
+    }
+
+    /// reads COLORFGBG ("fg;bg") when the terminal sets it, otherwise assumes dark
+    fn detect() -> Self {
+        let bg = std::env::var("COLORFGBG")
+            .ok()
+            .and_then(|colorfgbg| colorfgbg.rsplit(';').next()?.parse::<u8>().ok());
+        match bg {
+            Some(7) | Some(9..=15) => Background::Light,
+            _ => Background::Dark,
+        }
+    }
+}
+
+/// arrow pointing from a selected tile towards the next one
+fn ascii_path_glyph(relative_next: &TilePosition) -> char {
+    match (relative_next.y, relative_next.x) {
+        (-1, -1) | (1, 1) => '\\',
+        (-1, 1) | (1, -1) => '/',
+        (-1, 0) | (1, 0) => '|',
+        (0, -1) | (0, 1) => '-',
+        _ => unreachable!("unattainable TilePosition resulting from TilePosition::TryFrom<Wind8>"),
+    }
+}
+
+fn unicode_path_glyph(relative_next: &TilePosition) -> char {
+    match (relative_next.y, relative_next.x) {
+        (-1, -1) => '↖',
+        (-1, 0) => '↑',
+        (-1, 1) => '↗',
+        (0, -1) => '←',
+        (0, 1) => '→',
+        (1, -1) => '↙',
+        (1, 0) => '↓',
+        (1, 1) => '↘',
+        _ => unreachable!("unattainable TilePosition resulting from TilePosition::TryFrom<Wind8>"),
+    }
+}
+
+fn bg_fg_color_from_tile_type(
+    tile_type: TileType,
+    background: Background,
+    theme: &Theme,
+) -> (Color, Color) {
+    let themed = match background {
+        Background::Dark => theme.dark.get(tile_type),
+        Background::Light => theme.light.get(tile_type),
+    };
+    if let Some(colors) = themed {
+        return colors;
+    }
+    match background {
+        Background::Dark => match tile_type {
+            TileType::Potion => (Color::LightMagenta, Color::Black),
+            TileType::Shield => (Color::Blue, Color::Black),
+            TileType::Coin => (Color::Yellow, Color::Black),
+            TileType::Sword => (Color::Green, Color::Black),
+            TileType::Enemy => (Color::Red, Color::Black),
+            TileType::Special => (Color::White, Color::Black),
+            _ => (Color::Black, Color::White),
+        },
+        Background::Light => match tile_type {
+            TileType::Potion => (Color::Magenta, Color::White),
+            TileType::Shield => (Color::Blue, Color::White),
+            TileType::Coin => (Color::Yellow, Color::Black),
+            TileType::Sword => (Color::Green, Color::Black),
+            TileType::Enemy => (Color::Red, Color::White),
+            TileType::Special => (Color::Black, Color::White),
+            _ => (Color::White, Color::Black),
+        },
+    }
+}
+
+/// A view of a buffer limited to one area, taking positions relative to the area's top
+/// left; anything drawn outside it, or outside the buffer, is dropped instead of panicking.
+struct Clipped<'b> {
+    buf: &'b mut Buffer,
+    area: Rect,
+    // the part of `area` that's actually in the buffer
+    visible: Rect,
+}
+
+impl<'b> Clipped<'b> {
+    fn new(buf: &'b mut Buffer, area: Rect) -> Self {
+        // `Rect::intersection` underflows on rects that don't touch
+        let visible = if area.intersects(buf.area) {
+            area.intersection(buf.area)
+        } else {
+            Rect::new(area.x, area.y, 0, 0)
+        };
+        Self { buf, area, visible }
+    }
+
+    fn absolute(&self, x: u16, y: u16) -> Option<(u16, u16)> {
+        let (x, y) = (self.area.x.checked_add(x)?, self.area.y.checked_add(y)?);
+        let inside = x >= self.visible.left()
+            && x < self.visible.right()
+            && y >= self.visible.top()
+            && y < self.visible.bottom();
+        inside.then_some((x, y))
+    }
+
+    fn set_string<S: AsRef<str>>(&mut self, x: u16, y: u16, string: S, style: Style) {
+        if let Some((x, y)) = self.absolute(x, y) {
+            let width = (self.visible.right() - x) as usize;
+            self.buf.set_stringn(x, y, string, width, style);
+        }
+    }
+
+    fn cell(&mut self, x: u16, y: u16) -> Option<&mut ratatui::buffer::Cell> {
+        let (x, y) = self.absolute(x, y)?;
+        Some(self.buf.get_mut(x, y))
+    }
+
+    fn symbol(&self, x: u16, y: u16) -> Option<&str> {
+        let (x, y) = self.absolute(x, y)?;
+        Some(self.buf.get(x, y).symbol.as_str())
+    }
+
+    /// the visible part of `rect`, in buffer positions
+    fn visible_rect(&self, rect: Rect) -> Option<Rect> {
+        let rect = Rect::new(
+            self.area.x.saturating_add(rect.x),
+            self.area.y.saturating_add(rect.y),
+            rect.width,
+            rect.height,
+        );
+        rect.intersects(self.visible)
+            .then(|| rect.intersection(self.visible))
+    }
+
+    fn render_widget<W: Widget>(&mut self, widget: W, rect: Rect) {
+        if let Some(rect) = self.visible_rect(rect) {
+            widget.render(rect, self.buf);
+        }
+    }
+
+    fn render_stateful_widget<W: StatefulWidget>(
+        &mut self,
+        widget: W,
+        rect: Rect,
+        state: &mut W::State,
+    ) {
+        if let Some(rect) = self.visible_rect(rect) {
+            widget.render(rect, self.buf, state);
+        }
     }
 }
 
 struct GameWidget<'a> {
     pub game: &'a Game,
     pub cursor_pos: (u16, u16),
-    pub improvement_choice_selection_positions: &'a Vec<(u16, u16)>,
+    pub improvement_choice_indeces: &'a Vec<usize>,
+    pub improvement_list_state: &'a mut ListState,
+    pub status_message: Option<&'a str>,
+    pub draining_shields: Option<u64>,
+    pub objective_display: Option<String>,
+    pub background: Background,
+    pub theme: &'a Theme,
+    pub number_format: NumberFormat,
+    pub stat_display: StatDisplay,
+    // relative to the area the widget is rendered into, like every other position it draws at
+    pub hud_area: Rect,
+    pub selection_start_style: SelectionStartStyle,
+    pub new_ability_slots: &'a [usize],
+    pub incoming_damage_style: Style,
+    pub path_style: PathStyle,
+    pub show_guide_labels: bool,
+    pub show_hud: bool,
+    // double-width blots take an extra column per tile
+    pub wide_glyphs: bool,
+    // how to commit the improvement choices, when Enter is needed
+    pub improvement_confirm_hint: Option<&'static str>,
+    // path of the suggested move being shown, if any
+    pub hint_path: Option<&'a [TilePosition]>,
 }
-impl<'a> Widget for GameWidget<'a> {
-    fn render(self, _area: Rect, buf: &mut Buffer) {
-        // selection positions
-        for pos in self.improvement_choice_selection_positions.iter() {
-            buf.get_mut(pos.0, pos.1).set_bg(Color::White);
-        }
+impl<'a> GameWidget<'a> {
+    fn board(&self) -> BoardSize {
+        BoardSize::of(self.game, self.wide_glyphs)
+    }
+
+    /// status, stats, abilities and specials; returns the row after the last one drawn
+    fn render_hud(&self, buf: &mut Clipped) -> u16 {
+        // hud text
 
-        // below text
+        let hud_x = self.hud_area.x;
+        let mut text_y = self.hud_area.y;
 
-        let mut text_y = PLAYING_CURSOR_MAX_DOWN + 1;
+        // status message
+        if let Some(msg) = self.status_message {
+            buf.set_string(
+                hud_x,
+                text_y,
+                msg,
+                Style::default().add_modifier(Modifier::BOLD),
+            );
+        }
+        text_y += 1;
 
         // incoming damage
-        let incoming_damage_display = format!("incoming damage: {}", self.game.incoming_damage());
-        buf.set_string(0, text_y, incoming_damage_display, Style::default());
+        let mut incoming_damage_display =
+            format!("incoming damage: {}", self.game.incoming_damage());
+        // who it comes from, as damage@row,col; the attackers are also drawn reversed
+        let damage_sources: Vec<String> = self
+            .game
+            .incoming_damage_breakdown()
+            .into_iter()
+            .map(|(tp, damage)| format!("{}@{},{}", damage, tp.y, tp.x))
+            .collect();
+        if !damage_sources.is_empty() {
+            incoming_damage_display += &format!(" ({})", damage_sources.join(" "));
+        }
+        buf.set_string(
+            hud_x,
+            text_y,
+            &incoming_damage_display,
+            self.incoming_damage_style,
+        );
+        // enemies spawn more often as the run goes on
+        let spawn_ramp_level = self.game.spawn_weights().ramp_level;
+        if spawn_ramp_level > 0 {
+            buf.set_string(
+                hud_x + incoming_damage_display.len() as u16 + 1,
+                text_y,
+                format!("(ramp {})", spawn_ramp_level),
+                Style::default().fg(Color::DarkGray),
+            );
+        }
+        text_y += 1;
+        // what the chain being built is worth so far; the row is kept so the hud doesn't jump
+        if let Some(preview) = self.game.selection_preview() {
+            // the length turns green once the chain is long enough to drop
+            let selection_length = self.game.selection_length();
+            let missing = self
+                .game
+                .min_selection_length()
+                .saturating_sub(selection_length);
+            let length_display = format!("chain x{}", selection_length);
+            let length_style = if missing == 0 {
+                Style::default()
+                    .fg(Color::Green)
+                    .add_modifier(Modifier::BOLD)
+            } else {
+                Style::default()
+            };
+            buf.set_string(hud_x, text_y, &length_display, length_style);
+            let mut selection_display = format!(
+                ": {} dmg to {} enemies, +{} coins, +{} shields, +{} potions",
+                preview.sword_damage,
+                preview.enemies_hit,
+                preview.coins_gained,
+                preview.shields_gained,
+                preview.potions_gained
+            );
+            // healing past max hit points spills into the UP meter
+            if preview.overheal_shield_cents > 0 {
+                selection_display += &format!(
+                    ", +{} UP",
+                    self.number_format
+                        .format(preview.overheal_shield_cents as u64)
+                );
+            }
+            let selection_x = hud_x + length_display.len() as u16;
+            buf.set_string(selection_x, text_y, &selection_display, Style::default());
+            if missing > 0 {
+                buf.set_string(
+                    selection_x + selection_display.len() as u16 + 1,
+                    text_y,
+                    format!("(need {} more)", missing),
+                    Style::default().fg(Color::DarkGray),
+                );
+            }
+        }
         text_y += 1;
         // player stats and whatnot
         let hit_points_display = format!(
-            "hit points: {}/{}",
-            self.game.player().being.hit_points,
-            self.game.player().being.max_hit_points
+            "hit points: {}",
+            self.stat_display.format(
+                self.game.player().being.hit_points as u64,
+                self.game.player().being.max_hit_points as u64
+            )
         );
-        buf.set_string(0, text_y, hit_points_display, Style::default());
+        buf.set_string(hud_x, text_y, &hit_points_display, Style::default());
+        // temporary hit points from overhealing, which decay at the end of each turn
+        if self.game.player().being.temporary_hit_points > 0 {
+            buf.set_string(
+                hud_x + hit_points_display.len() as u16 + 1,
+                text_y,
+                format!("(+{})", self.game.player().being.temporary_hit_points),
+                Style::default().fg(Color::LightMagenta),
+            );
+        }
         text_y += 1;
         let shields_display = format!(
-            "shields: {}/{}",
-            self.game.player().being.shields,
-            self.game.player().being.max_shields
+            "shields: {}",
+            self.stat_display.format(
+                self.game.player().being.shields as u64,
+                self.game.player().being.max_shields as u64
+            )
         );
-        buf.set_string(0, text_y, shields_display, Style::default());
+        buf.set_string(hud_x, text_y, &shields_display, Style::default());
+        let shields = self.game.player().being.shields as u64;
+        let max_shields = self.game.player().being.max_shields as u64;
+        if max_shields > 0 {
+            let displayed_shields = self.draining_shields.unwrap_or(shields);
+            let gauge_x = hud_x + shields_display.len() as u16 + 1;
+            for i in 0..SHIELD_GAUGE_WIDTH {
+                let (gauge_char, gauge_color) = if i * max_shields < shields * SHIELD_GAUGE_WIDTH {
+                    ('#', Color::Blue)
+                } else if i * max_shields < displayed_shields * SHIELD_GAUGE_WIDTH {
+                    ('#', Color::Red)
+                } else {
+                    ('.', Color::DarkGray)
+                };
+                if let Some(cell) = buf.cell(gauge_x + i as u16, text_y) {
+                    cell.set_char(gauge_char).set_fg(gauge_color);
+                }
+            }
+        }
         text_y += 1;
         let coins_display = format!(
             "coins: {}/{}",
-            self.game.player().coin_cents,
-            self.game.player().coin_cents_per_purchase
+            self.number_format
+                .format(self.game.player().coin_cents as u64),
+            self.number_format
+                .format(self.game.player().coin_cents_per_purchase as u64)
         );
-        buf.set_string(0, text_y, coins_display, Style::default());
+        buf.set_string(hud_x, text_y, coins_display, Style::default());
         text_y += 1;
         let up_display = format!(
             "UP: {}/{}",
-            self.game.player().excess_shield_cents,
-            self.game.player().excess_shield_cents_per_upgrade
+            self.number_format
+                .format(self.game.player().excess_shield_cents as u64),
+            self.number_format
+                .format(self.game.player().excess_shield_cents_per_upgrade as u64)
         );
-        buf.set_string(0, text_y, up_display, Style::default());
+        buf.set_string(hud_x, text_y, up_display, Style::default());
         text_y += 1;
         let xp_display = format!(
-            "XP: {}/{}",
-            self.game.player().experience_point_cents,
-            self.game.player().experience_point_cents_per_level_up
+            "level {} XP: {}/{}",
+            self.game.player().level(),
+            self.number_format
+                .format(self.game.player().experience_point_cents as u64),
+            self.number_format
+                .format(self.game.player().experience_point_cents_per_level_up as u64)
         );
-        buf.set_string(0, text_y, xp_display, Style::default());
-        text_y += 2;
+        buf.set_string(hud_x, text_y, xp_display, Style::default());
+        text_y += 1;
+        let score_display = format!("score: {}", self.game.score());
+        buf.set_string(hud_x, text_y, score_display, Style::default());
+        text_y += 1;
+        if let Some(ref objective_display) = self.objective_display {
+            buf.set_string(hud_x, text_y, objective_display, Style::default());
+        }
+        text_y += 1;
         // player abilities
-        for (idx, ability_opt) in self.game.player().abilities.iter().enumerate() {
-            let mut ability_string = format!("{} - ", idx + 1);
-            match ability_opt {
-                Some(a) => {
-                    let (name, _) = a.ability_type.name_description();
-                    ability_string += name;
-                    buf.set_string(0, text_y, ability_string, Style::default());
-                    text_y += 1;
-                    if a.running_cooldown > 0 {
-                        buf.set_string(
-                            4,
-                            text_y,
-                            format!("COOLDOWN: {}", a.running_cooldown),
-                            Style::default(),
-                        );
-                        text_y += 1;
+        if self.game.player().abilities.iter().all(|a| a.is_none()) {
+            buf.set_string(hud_x, text_y, "no abilities yet", Style::default());
+            text_y += 1;
+        }
+        let ability_rows: Vec<Row> = self
+            .game
+            .player()
+            .abilities
+            .iter()
+            .enumerate()
+            .map(|(idx, ability_opt)| {
+                let (name, cooldown) = match ability_opt {
+                    Some(a) => {
+                        let (name, _) = a.ability_type.name_description();
+                        // always on while equipped, so there's nothing to cool down
+                        let cooldown = if a.ability_type.is_passive() {
+                            String::from("[passive]")
+                        } else if a.running_cooldown > 0 {
+                            format!("COOLDOWN: {}", a.running_cooldown)
+                        } else {
+                            String::from("ready")
+                        };
+                        (String::from(name), cooldown)
                     }
+                    None => (String::from("[empty]"), String::new()),
+                };
+                let row = Row::new(vec![format!("{}", idx + 1), name, cooldown]);
+                if self.new_ability_slots.contains(&idx) {
+                    row.style(
+                        Style::default()
+                            .fg(Color::Green)
+                            .add_modifier(Modifier::BOLD),
+                    )
+                } else {
+                    row
                 }
-                None => {
-                    ability_string += "[empty]";
-                    buf.set_string(0, text_y, ability_string, Style::default());
+            })
+            .collect();
+        let ability_rows_len = ability_rows.len() as u16;
+        let ability_widths = [
+            Constraint::Length(4),
+            Constraint::Length(20),
+            Constraint::Length(12),
+        ];
+        let ability_table = Table::new(ability_rows)
+            .header(
+                Row::new(vec!["slot", "ability", "cooldown"])
+                    .style(Style::default().add_modifier(Modifier::UNDERLINED)),
+            )
+            .widths(&ability_widths);
+        buf.render_widget(
+            ability_table,
+            Rect::new(hud_x, text_y, self.hud_area.width, ability_rows_len + 1),
+        );
+        text_y += ability_rows_len + 1;
+        text_y += 1;
+        // current special, or a one line summary when there are several
+        let specials_vec = self.game.specials();
+        if specials_vec.len() > 1 {
+            let special_summary = format!(
+                "Special Monsters: {} on the board (P for details)",
+                specials_vec.len()
+            );
+            buf.set_string(hud_x, text_y, special_summary, Style::default());
+            text_y += 1;
+        } else {
+            for (_tp, t, _sid) in specials_vec {
+                if let TileInfo::Special(special) = t.tile_info {
+                    let (name, desc) = special.special_type.name_description();
+                    let special_display = format!(
+                        "Special Monster: {} - {} ({})",
+                        name,
+                        desc,
+                        special_readiness(special.running_cooldown as u32)
+                    );
+                    buf.set_string(hud_x, text_y, special_display, Style::default());
                     text_y += 1;
+                } else {
+                    unreachable!(
+                        "Game::specials() gave a tile with tile.tile_info NOT TileInfo::Special(_)"
+                    );
                 }
-            };
+            }
         }
-        text_y += 1;
-        // current special
-        let specials_vec = self.game.specials();
-        for (_tp, t, _sid) in specials_vec {
-            if let TileInfo::Special(special) = t.tile_info {
-                let (name, desc) = special.special_type.name_description();
-                let special_display = format!("Special Monster: {} - {}", name, desc);
-                buf.set_string(0, text_y, special_display, Style::default());
-                text_y += 1;
-            } else {
-                unreachable!(
-                    "Game::specials() gave a tile with tile.tile_info NOT TileInfo::Special(_)"
-                );
+        // boss hit points
+        if let Some(boss) = boss_status(self.game) {
+            let boss_display = format!(
+                "BOSS {}{}: {}/{} ",
+                boss.name,
+                if boss.enraged { " (Enraged)" } else { "" },
+                boss.hit_points,
+                boss.max_hit_points
+            );
+            buf.set_string(
+                hud_x,
+                text_y,
+                &boss_display,
+                Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+            );
+            let bar_x = hud_x + boss_display.len() as u16;
+            for i in 0..BOSS_HP_BAR_WIDTH {
+                let filled = i * boss.max_hit_points < boss.hit_points * BOSS_HP_BAR_WIDTH;
+                if let Some(cell) = buf.cell(bar_x + i as u16, text_y) {
+                    cell.set_char(if filled { '#' } else { '.' })
+                        .set_fg(if filled { Color::Red } else { Color::DarkGray });
+                }
             }
+            text_y += 1;
         }
+        text_y
+    }
+}
+impl<'a> Widget for GameWidget<'a> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        // every position below is relative to `area`, and whatever falls outside is dropped
+        let buf = &mut Clipped::new(buf, area);
+        let hud_x = self.hud_area.x;
+        // the hud can be hidden to leave only the board
+        let text_y = if self.show_hud {
+            self.render_hud(buf)
+        } else {
+            self.hud_area.y
+        };
 
         // improvement choice or board
 
         match self.game.improvement_choice_set() {
             Some(set) => {
                 // improvement choice
-                let mut choice_text_y = 0;
-                buf.set_string(0, choice_text_y, String::from(set.header), Style::default());
-                choice_text_y += 1;
-                for display in set.displays.iter() {
+                buf.set_string(0, 0, String::from(set.header), Style::default());
+                // running count, with the confirm hint lit once enough are chosen
+                let num_selected = self.improvement_choice_indeces.len();
+                let count_display = format!(" selected {} of {}", num_selected, set.num_to_choose);
+                let count_x = set.header.len() as u16;
+                buf.set_string(
+                    count_x,
+                    0,
+                    &count_display,
+                    Style::default().add_modifier(Modifier::BOLD),
+                );
+                if let Some(hint) = self.improvement_confirm_hint {
+                    let hint_style = if num_selected == set.num_to_choose {
+                        Style::default().fg(Color::Black).bg(Color::Green)
+                    } else {
+                        Style::default().fg(Color::DarkGray)
+                    };
                     buf.set_string(
-                        1,
-                        choice_text_y,
-                        display.description.as_str(),
-                        Style::default(),
+                        count_x + count_display.len() as u16 + 1,
+                        0,
+                        format!("({})", hint),
+                        hint_style,
+                    );
+                }
+                let items: Vec<ListItem> = set
+                    .displays
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, display)| {
+                        let item = ListItem::new(display.description.as_str());
+                        if self.improvement_choice_indeces.contains(&idx) {
+                            item.style(Style::default().bg(Color::White).fg(Color::Black))
+                        } else {
+                            item
+                        }
+                    })
+                    .collect();
+                let list = List::new(items)
+                    .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+                    .highlight_symbol(">");
+                let board = self.board();
+                // a HUD beside the board starts right after it, so keep to the board's columns
+                let list_width = if self.hud_area.x > 0 {
+                    (board.max_right() + 1).min(area.width)
+                } else {
+                    area.width
+                };
+                let mut list_area = Rect::new(0, 1, list_width, board.max_down());
+                if !self.improvement_choice_indeces.is_empty() {
+                    // what the player would look like if the current choices were confirmed
+                    let chunks = Layout::default()
+                        .direction(Direction::Horizontal)
+                        .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+                        .split(list_area);
+                    list_area = chunks[0];
+                    let current = self.game.player();
+                    let preview = self
+                        .game
+                        .preview_improvements(self.improvement_choice_indeces);
+                    let stat_changes = [
+                        (
+                            "hit points",
+                            current.being.max_hit_points as u64,
+                            preview.being.max_hit_points as u64,
+                        ),
+                        (
+                            "shields",
+                            current.being.max_shields as u64,
+                            preview.being.max_shields as u64,
+                        ),
+                        (
+                            "damage",
+                            current.being.base_output_damage as u64,
+                            preview.being.base_output_damage as u64,
+                        ),
+                        (
+                            "coins per purchase",
+                            current.coin_cents_per_purchase as u64,
+                            preview.coin_cents_per_purchase as u64,
+                        ),
+                        (
+                            "UP per upgrade",
+                            current.excess_shield_cents_per_upgrade as u64,
+                            preview.excess_shield_cents_per_upgrade as u64,
+                        ),
+                        (
+                            "XP per level",
+                            current.experience_point_cents_per_level_up as u64,
+                            preview.experience_point_cents_per_level_up as u64,
+                        ),
+                    ];
+                    let mut preview_lines: Vec<Spans> = stat_changes
+                        .iter()
+                        .filter(|(_, before, after)| before != after)
+                        .map(|(label, before, after)| {
+                            Spans::from(format!("{} {} → {}", label, before, after))
+                        })
+                        .collect();
+                    for (idx, (before, after)) in current
+                        .abilities
+                        .iter()
+                        .zip(preview.abilities.iter())
+                        .enumerate()
+                    {
+                        let name = |ability_opt: &Option<_>| match ability_opt {
+                            Some(a) => a.ability_type.name_description().0,
+                            None => "[empty]",
+                        };
+                        if name(before) != name(after) {
+                            preview_lines.push(Spans::from(format!(
+                                "slot {}: {} → {}",
+                                idx + 1,
+                                name(before),
+                                name(after)
+                            )));
+                        }
+                    }
+                    buf.render_widget(
+                        Paragraph::new(preview_lines)
+                            .block(Block::default().title("Preview").borders(Borders::ALL)),
+                        chunks[1],
                     );
-                    choice_text_y += 1;
                 }
+                buf.render_stateful_widget(list, list_area, self.improvement_list_state);
             }
             None => {
                 // board
-                {
-                    let hover_tile = self
-                        .game
-                        .get_tile(&tile_position_from_cursor_position(self.cursor_pos))
-                        .expect("");
+                let damage_breakdown = self.game.incoming_damage_breakdown();
+                if self.show_hud {
+                    let hover_position =
+                        tile_position_from_cursor_position(self.cursor_pos, self.board());
                     let mut hover_string = String::from("Hovered Tile: ");
-                    hover_string += match hover_tile.tile_type {
-                        TileType::Potion => "Potion",
-                        TileType::Shield => "Shield",
-                        TileType::Coin => "Coin",
-                        TileType::Sword => "Sword",
-                        TileType::Enemy => "Enemy",
-                        TileType::Special => "Special",
-                        _ => unreachable!(""),
-                    };
-                    let info_string;
-                    match hover_tile.tile_info {
-                        TileInfo::Enemy(b) => {
-                            info_string = format!(
-                                " {{ hp: {}, sh: {}, dmg: {} }}",
-                                b.hit_points, b.shields, b.base_output_damage
-                            )
+                    // off the board, e.g. right after a resize
+                    if let Some(hover_tile) = self.game.get_tile(&hover_position) {
+                        hover_string += match hover_tile.tile_type {
+                            TileType::Potion => "Potion",
+                            TileType::Shield => "Shield",
+                            TileType::Coin => "Coin",
+                            TileType::Sword => "Sword",
+                            TileType::Enemy => "Enemy",
+                            TileType::Special => "Special",
+                            _ => unreachable!(""),
+                        };
+                        let info_string;
+                        match hover_tile.tile_info {
+                            TileInfo::Enemy(b) => {
+                                info_string = format!(
+                                    " {{ hp: {}, sh: {}, dmg: {} }}",
+                                    b.hit_points, b.shields, b.base_output_damage
+                                )
+                            }
+                            TileInfo::Special(s) => {
+                                info_string = format!(
+                                    " {{ type: {}, hp: {}, sh: {}, dmg: {} }}",
+                                    s.special_type.name_description().0,
+                                    s.being.hit_points,
+                                    s.being.shields,
+                                    s.being.base_output_damage
+                                )
+                            }
+                            TileInfo::None => info_string = String::from(""),
+                        };
+                        hover_string += info_string.as_str();
+                        if let TileType::Enemy | TileType::Special = hover_tile.tile_type {
+                            match damage_breakdown
+                                .iter()
+                                .find(|(tp, _)| *tp == hover_position)
+                            {
+                                Some((_, damage)) => {
+                                    hover_string += format!(
+                                        " attacking for {} of {}",
+                                        damage,
+                                        self.game.incoming_damage()
+                                    )
+                                    .as_str()
+                                }
+                                None => hover_string += " dormant",
+                            };
+                        }
+                    } else {
+                        hover_string += "none";
+                    }
+                    buf.set_string(hud_x, text_y, hover_string, Style::default());
+                }
+                // projected result of dropping the current selection
+                if self.show_hud && self.game.get_selection_start().is_some() {
+                    if let Some(outcome) = self.game.preview_drop_selection() {
+                        let mut preview_string = format!(
+                            "drop: {} dmg, +{} coins, +{} XP, +{} hp",
+                            outcome.damage_dealt,
+                            self.number_format.format(outcome.coin_cents_gained as u64),
+                            self.number_format
+                                .format(outcome.experience_point_cents_gained as u64),
+                            outcome.hit_points_gained
+                        );
+                        // a sword chain cuts through the enemies it passes, and counts the
+                        // ones it kills the same as the rest of the chain
+                        if outcome.enemies_killed > 0 {
+                            preview_string +=
+                                format!(", kills {}", outcome.enemies_killed).as_str();
                         }
-                        TileInfo::Special(s) => {
-                            info_string = format!(
-                                " {{ type: {}, hp: {}, sh: {}, dmg: {} }}",
-                                s.special_type.name_description().0,
-                                s.being.hit_points,
-                                s.being.shields,
-                                s.being.base_output_damage
-                            )
+                        if outcome.incoming_damage > 0 {
+                            preview_string +=
+                                format!(", then incoming {}", outcome.incoming_damage).as_str();
                         }
-                        TileInfo::None => info_string = String::from(""),
-                    };
-                    hover_string += info_string.as_str();
-                    buf.set_string(0, text_y, hover_string, Style::default());
-                }
-                for x in 0..(DEFAULT_BOARD_WIDTH as u16) {
-                    let blot_x = x * 2;
-                    for y in 0..(DEFAULT_BOARD_HEIGHT as u16) {
-                        let blot_y = y * 2;
-                        let t: Tile = self
+                        buf.set_string(hud_x, text_y + 1, preview_string, Style::default());
+                    }
+                }
+                // guide labels sit in the spare arrow row under the board and the spare
+                // arrow column to its right, so the board and cursor math stay put
+                let board = self.board();
+                if self.show_guide_labels {
+                    let label_style = Style::default().fg(Color::DarkGray);
+                    for x in 0..board.width {
+                        buf.set_string(
+                            PLAYING_CURSOR_MAX_LEFT + x * board.stride,
+                            board.max_down(),
+                            format!("{}", x % 10),
+                            label_style,
+                        );
+                    }
+                    for y in 0..board.height {
+                        buf.set_string(
+                            board.max_right(),
+                            PLAYING_CURSOR_MAX_UP + y * PLAYING_CURSOR_MOVE,
+                            format!("{}", y % 10),
+                            label_style,
+                        );
+                    }
+                }
+                let attacking_positions: Vec<TilePosition> = damage_breakdown
+                    .into_iter()
+                    .map(|(tp, _damage)| tp)
+                    .collect();
+                // empty without a selection
+                let selectable_positions = self.game.selectable_neighbors();
+                // held by a Warden until it dies
+                let blocked_positions = self.game.blocked_positions();
+                for x in 0..board.width {
+                    let blot_x = x * board.stride;
+                    for y in 0..board.height {
+                        let blot_y = y * PLAYING_CURSOR_MOVE;
+                        // a position the core doesn't know is left as a blank cell
+                        let t: Tile = match self
                             .game
                             .get_tile(&TilePosition::new(y as isize, x as isize))
-                            .expect("plz");
-                        let blot = blot_char_from_tile_type(t.tile_type);
-                        let (bg_color, fg_color) = bg_fg_color_from_tile_type(t.tile_type);
+                        {
+                            Some(t) => t,
+                            None => continue,
+                        };
+                        let mut blot = blot_char_from_tile_type(t.tile_type, self.theme);
+                        let (bg_color, fg_color) =
+                            bg_fg_color_from_tile_type(t.tile_type, self.background, self.theme);
                         let mut style = Style::default().bg(bg_color).fg(fg_color);
                         match self.game.get_selection_start() {
                             Some(pos) => {
                                 if pos == TilePosition::new(y as isize, x as isize) {
-                                    style = style.add_modifier(Modifier::RAPID_BLINK);
+                                    match self.selection_start_style {
+                                        SelectionStartStyle::Blink => {
+                                            style = style.add_modifier(Modifier::RAPID_BLINK)
+                                        }
+                                        SelectionStartStyle::Reversed => {
+                                            if attacking_positions.contains(&pos) {
+                                                blot = '@'
+                                            } else {
+                                                style = style.add_modifier(Modifier::REVERSED)
+                                            }
+                                        }
+                                        SelectionStartStyle::Glyph => blot = '@',
+                                    };
                                 }
                             }
                             None => {}
                         };
-                        buf.get_mut(blot_x, blot_y).set_style(style).set_char(blot);
+                        if blocked_positions.contains(&TilePosition::new(y as isize, x as isize)) {
+                            style = style.add_modifier(Modifier::DIM);
+                        }
+                        // legal next steps for the chain are underlined
+                        if selectable_positions.contains(&TilePosition::new(y as isize, x as isize))
+                        {
+                            style = style.add_modifier(Modifier::UNDERLINED);
+                        }
+                        // tiles on the suggested path are bold, the underline is taken by
+                        // the legal next steps
+                        if let Some(path) = self.hint_path {
+                            if path.contains(&TilePosition::new(y as isize, x as isize)) {
+                                style = style.add_modifier(Modifier::BOLD);
+                            }
+                        }
+                        // enemies that will attack this turn are drawn reversed
+                        if attacking_positions.contains(&TilePosition::new(y as isize, x as isize))
+                        {
+                            style = style.add_modifier(Modifier::REVERSED);
+                        }
+                        if let Some(cell) = buf.cell(blot_x, blot_y) {
+                            cell.set_style(style).set_char(blot);
+                        }
+                        // the second column of a wide blot, which a narrow one leaves blank
+                        if board.blot_width() > 1 {
+                            if let Some(cell) = buf.cell(blot_x + 1, blot_y) {
+                                cell.set_style(style).set_char(' ');
+                            }
+                        }
                         let mut arrow_blot_x = blot_x;
                         let mut arrow_blot_y = blot_y;
                         let mut arrow_blot: char;
@@ -383,18 +1515,48 @@ impl<'a> Widget for GameWidget<'a> {
                             }},
                             _ => unreachable!("unattainable TilePosition resulting from TilePosition::TryFrom<Wind8>") ,
                         };
-                                match tp.x {
-                                    -1 => arrow_blot_x -= 1,
-                                    1 => arrow_blot_x += 1,
-                                    _ => {}
-                                };
+                                arrow_blot_x = board.arrow_column(blot_x, tp.x);
+                                if self.path_style == PathStyle::Unicode {
+                                    arrow_blot = unicode_path_glyph(&tp);
+                                }
                             }
                         };
-                        match buf.get(arrow_blot_x, arrow_blot_y).symbol.chars().next() {
+                        // two diagonals crossing between the same four tiles
+                        match buf
+                            .symbol(arrow_blot_x, arrow_blot_y)
+                            .and_then(|symbol| symbol.chars().next())
+                        {
                             Some('/') | Some('\\') => arrow_blot = 'X',
+                            Some('↗') | Some('↖') | Some('↘') | Some('↙') => {
+                                arrow_blot = '╳'
+                            }
                             _ => {}
                         }
-                        buf.get_mut(arrow_blot_x, arrow_blot_y).set_char(arrow_blot);
+                        if let Some(cell) = buf.cell(arrow_blot_x, arrow_blot_y) {
+                            cell.set_char(arrow_blot);
+                        }
+                    }
+                }
+                // the suggested path gets dark gray connectors in the gaps the chain's own
+                // arrows would use, leaving any real arrow already there alone
+                if let Some(path) = self.hint_path {
+                    for pair in path.windows(2) {
+                        let step = TilePosition::new(pair[1].y - pair[0].y, pair[1].x - pair[0].x);
+                        let connector_x =
+                            board.arrow_column(pair[0].x as u16 * board.stride, step.x);
+                        let connector_y =
+                            (pair[0].y * PLAYING_CURSOR_MOVE as isize + step.y) as u16;
+                        if buf.symbol(connector_x, connector_y) != Some(" ") {
+                            continue;
+                        }
+                        let connector = if self.path_style == PathStyle::Unicode {
+                            unicode_path_glyph(&step)
+                        } else {
+                            ascii_path_glyph(&step)
+                        };
+                        if let Some(cell) = buf.cell(connector_x, connector_y) {
+                            cell.set_char(connector).set_fg(Color::DarkGray);
+                        }
                     }
                 }
             }
@@ -402,16 +1564,337 @@ impl<'a> Widget for GameWidget<'a> {
     }
 }
 
-fn run_app<B: Backend>(terminal: &mut Terminal<B>) -> io::Result<()> {
-    let mut game = Game::default();
-    let mut playing_cursor_position: (u16, u16) = (0, 0);
-    let mut choosing_improvement_cursor_position: (u16, u16) = (0, 1);
-    let mut improvement_choice_indeces: Vec<usize> = vec![];
-    let mut improvement_choice_selection_positions: Vec<(u16, u16)> = vec![];
-    let mut game_state: GameState;
-    terminal.show_cursor()?;
-    loop {
-        game_state = match game.improvement_choice_set() {
+// pulse period of the incoming damage line, from harmless down to lethal
+const DAMAGE_PULSE_SLOWEST: Duration = Duration::from_millis(2000);
+const DAMAGE_PULSE_FASTEST: Duration = Duration::from_millis(500);
+
+/// red pulse on the incoming damage line, quicker the closer the hit comes to lethal;
+/// steady red under reduced motion
+fn incoming_damage_style(game: &Game, reduced_motion: bool, elapsed: Duration) -> Style {
+    let incoming = game.incoming_damage() as u64;
+    if incoming == 0 {
+        return Style::default();
+    }
+    let red = Style::default().fg(Color::Red);
+    if reduced_motion {
+        return red;
+    }
+    let being = &game.player().being;
+    let survivable = (being.hit_points + being.shields) as u64;
+    let lethality = (incoming as f64 / survivable.max(1) as f64).min(1.0);
+    let period = DAMAGE_PULSE_SLOWEST.as_millis() as f64
+        - (DAMAGE_PULSE_SLOWEST - DAMAGE_PULSE_FASTEST).as_millis() as f64 * lethality;
+    if (elapsed.as_millis() as f64 % period) < period / 2.0 {
+        red.add_modifier(Modifier::BOLD)
+    } else {
+        red
+    }
+}
+
+/// read-only board with one blot per tile and no arrow gaps, for keeping context in menus
+struct BoardPreview<'a> {
+    game: &'a Game,
+    background: Background,
+    theme: &'a Theme,
+    wide_glyphs: bool,
+}
+impl<'a> Widget for BoardPreview<'a> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let block = Block::default().title("Board").borders(Borders::ALL);
+        let inner = block.inner(area);
+        block.render(area, buf);
+        let board = BoardSize::of(self.game, self.wide_glyphs);
+        for tp in board.tile_positions() {
+            let (x, y) = (
+                inner.x + tp.x as u16 * board.blot_width(),
+                inner.y + tp.y as u16,
+            );
+            if x + board.blot_width() > inner.right() || y >= inner.bottom() {
+                continue;
+            }
+            if let Some(t) = self.game.get_tile(&tp) {
+                let (bg_color, fg_color) =
+                    bg_fg_color_from_tile_type(t.tile_type, self.background, self.theme);
+                buf.get_mut(x, y)
+                    .set_char(blot_char_from_tile_type(t.tile_type, self.theme))
+                    .set_style(Style::default().bg(bg_color).fg(fg_color));
+            }
+        }
+    }
+}
+
+/// session state that sits around the core `Game`
+struct App {
+    game: Game,
+    // kept to start the same kind of run again
+    args: Args,
+    mode: GameMode,
+    background: Background,
+    theme: Theme,
+    // from the layout settings, or the theme's blots when they don't say
+    wide_glyphs: bool,
+    // which way tiles fall and where new ones come in
+    gravity: GravityDirection,
+    playing_cursor_position: (u16, u16),
+    improvement_list_state: ListState,
+    improvement_choice_indeces: Vec<usize>,
+    progress: RunProgress,
+    achievements: UnlockedAchievements,
+    lifetime_stats: LifetimeStats,
+    seed_bests: SeedBests,
+    // the seed's best before this run finished, None until it has or on a new seed
+    previous_seed_best: Option<u64>,
+    // what the finished run beat, for the banner on the game over screen
+    new_best: Option<&'static str>,
+    status_message: Option<StatusMessage>,
+    shield_drain: Option<ShieldDrain>,
+    menu: Option<Menu>,
+    menu_state: ListState,
+    confirming_quit: bool,
+    showing_help: bool,
+    paused: bool,
+    settings: Settings,
+    jump_cycle: Option<JumpCycle>,
+    boss_present: bool,
+    boss_enraged: bool,
+    // how many specials were on the board after the last turn, to announce new ones
+    specials_on_board: usize,
+    spawn_ramp_level: u32,
+    level: u32,
+    // (dy, dx) steps of the last dropped chain, for repeating its shape
+    last_selection_offsets: Vec<(isize, isize)>,
+    // `g` was pressed and the next key names the tile type to jump to
+    awaiting_jump_glyph: bool,
+    // a turn has resolved since the run was last saved
+    dirty_since_save: bool,
+    // SAVE_FILE holds this run, because it was resumed from there or saved this session
+    owns_save: bool,
+    debug: bool,
+    // most recent last; only shown under --debug
+    debug_log: Vec<String>,
+    choosing_loadout: bool,
+    loadout_list_state: ListState,
+    // indeces into Game::available_ability_types() picked for the starting loadout, in slot order
+    loadout_choice_indeces: Vec<usize>,
+    // how many times r has shuffled the loadout, so each press deals a new one
+    loadout_shuffles: u64,
+    // selected slot while the ability screen is open, closed when nothing is selected
+    ability_list_state: ListState,
+    // `d` was pressed once on the ability screen
+    confirming_ability_drop: bool,
+    flash: Option<Flash>,
+    // only the board is drawn, for screenshots
+    hud_hidden: bool,
+    // turn summaries, oldest first, each expiring on its own
+    toasts: Vec<StatusMessage>,
+    // a danger notification wants the terminal bell on the next loop
+    ring_bell: bool,
+    // drives the incoming damage pulse
+    started_at: Instant,
+    // first improvement shown, tracked alongside the list's own scrolling for mouse clicks
+    improvement_scroll_offset: usize,
+    // Space was pressed once on a drop over the big swing threshold
+    confirming_big_swing: bool,
+    // Enter was pressed once under the enter-with-preview setting
+    confirming_improvements: bool,
+    // ability slots filled by the last improvement, marked in the HUD for a turn
+    new_ability_slots: Vec<usize>,
+    // when and where `x` was last pressed, for spotting a double press
+    last_select_press: Option<(Instant, TilePosition)>,
+    // stands in for the wall clock when verifying a replay, moved on by each recorded gap
+    key_clock: Option<Instant>,
+    // tile under the held left button, so a drag selects each tile once
+    last_mouse_tile: Option<TilePosition>,
+    // size of the last drawn frame, so input handling needn't query the terminal
+    frame_size: Rect,
+    // tiles of a drop being resolved step by step under --debug
+    stepped_drop: Option<(Vec<(TilePosition, TileType)>, StatTotals)>,
+    // a resolved drop whose tiles are still falling into place, see settle_step
+    settling_drop: Option<TurnSummary>,
+    // the game and progress from just before the last drop, for a one turn undo
+    undo: Option<(GameSnapshot, RunProgress)>,
+    // the slot of a cast ability still waiting for its target tile
+    targeting_slot: Option<usize>,
+    // text typed after `:`, while the prompt is open
+    command_input: Option<String>,
+    // suggested moves, best first, and which one is being shown
+    hints: Vec<SuggestedMove>,
+    hint_index: usize,
+    mouse_event_seen: bool,
+    mouse_hint_shown: bool,
+    // time spent on screens that take mouse input
+    mouse_screen_time: Duration,
+}
+
+impl App {
+    fn new(args: &Args) -> Self {
+        Self::with_settings(args, Settings::load())
+    }
+
+    fn board(&self) -> BoardSize {
+        BoardSize::of(&self.game, self.wide_glyphs)
+    }
+
+    /// like `new` but with the settings given, e.g. a replay's pinned ones
+    fn with_settings(args: &Args, settings: Settings) -> Self {
+        let args = args.clone();
+        let mut improvement_list_state = ListState::default();
+        improvement_list_state.select(Some(0));
+        let mut loadout_list_state = ListState::default();
+        loadout_list_state.select(Some(0));
+        // a recording has to start from its seed, not from wherever the save left off,
+        // and an explicit seed or board asks for a new run rather than the saved one
+        let starts_fresh = args.seed.is_some() || args.board.is_some();
+        let saved_game = if args.record.is_none() && args.replay.is_none() && !starts_fresh {
+            load_saved_game()
+        } else {
+            None
+        };
+        let skipped_save = args.record.is_none()
+            && args.replay.is_none()
+            && starts_fresh
+            && std::path::Path::new(SAVE_FILE).exists();
+        let resumed = saved_game.is_some();
+        let (saved_game, saved_progress) = saved_game.unzip();
+        let mut game = match (saved_game, args.board) {
+            (Some(game), _) => game,
+            (None, Some((width, height))) => Game::with_dimensions(width, height),
+            (None, None) => match args.seed {
+                Some(seed) => Game::with_seed(seed),
+                None => Game::default(),
+            },
+        };
+        game.set_gravity_direction(args.gravity);
+        // a resumed run keeps the difficulty it was started on
+        if !resumed {
+            game.set_difficulty(args.difficulty);
+        }
+        if let Some(length) = settings.min_selection_length {
+            game.set_min_selection_length(length);
+        }
+        if settings.four_way_selection {
+            game.set_selection_mode(SelectionMode::FourWay);
+        }
+        let theme = Theme::load();
+        let wide_glyphs = settings
+            .layout
+            .wide_glyphs
+            .unwrap_or_else(|| theme.has_wide_blots());
+        let mut app = Self {
+            level: game.player().level(),
+            game,
+            mode: args.mode,
+            background: args.background,
+            theme,
+            wide_glyphs,
+            gravity: args.gravity,
+            debug: args.debug,
+            playing_cursor_position: (0, 0),
+            improvement_list_state,
+            improvement_choice_indeces: vec![],
+            progress: saved_progress.unwrap_or_default(),
+            achievements: UnlockedAchievements::load(),
+            lifetime_stats: LifetimeStats::load(),
+            seed_bests: SeedBests::load(),
+            previous_seed_best: None,
+            new_best: None,
+            status_message: None,
+            shield_drain: None,
+            menu: None,
+            menu_state: ListState::default(),
+            confirming_quit: false,
+            showing_help: false,
+            paused: false,
+            settings,
+            jump_cycle: None,
+            boss_present: false,
+            boss_enraged: false,
+            specials_on_board: 0,
+            spawn_ramp_level: 0,
+            last_selection_offsets: vec![],
+            awaiting_jump_glyph: false,
+            dirty_since_save: false,
+            owns_save: resumed,
+            debug_log: vec![],
+            command_input: None,
+            stepped_drop: None,
+            settling_drop: None,
+            undo: None,
+            targeting_slot: None,
+            frame_size: Rect::default(),
+            last_mouse_tile: None,
+            last_select_press: None,
+            key_clock: None,
+            new_ability_slots: vec![],
+            confirming_improvements: false,
+            confirming_big_swing: false,
+            improvement_scroll_offset: 0,
+            flash: None,
+            toasts: vec![],
+            hud_hidden: false,
+            ring_bell: false,
+            started_at: Instant::now(),
+            ability_list_state: ListState::default(),
+            // a resumed run already picked its loadout
+            choosing_loadout: !resumed,
+            loadout_list_state,
+            loadout_choice_indeces: vec![],
+            loadout_shuffles: 0,
+            confirming_ability_drop: false,
+            hints: vec![],
+            hint_index: 0,
+            mouse_event_seen: false,
+            mouse_hint_shown: false,
+            mouse_screen_time: Duration::ZERO,
+            args,
+        };
+        if skipped_save {
+            app.set_status_message(format!(
+                "started a new run, {} is left for a run without --seed or --board",
+                SAVE_FILE
+            ));
+        }
+        let unparseable = persist::unparseable_files();
+        if !unparseable.is_empty() {
+            app.set_status_message(format!(
+                "couldn't parse {}, using defaults and not saving over it, see log.txt",
+                unparseable.join(", ")
+            ));
+        }
+        app
+    }
+
+    /// a fresh run with the same options, keeping what's about the session rather than the run
+    fn restart(&mut self) {
+        let mut fresh = App::with_settings(&self.args, self.settings.clone());
+        fresh.mouse_event_seen = self.mouse_event_seen;
+        fresh.mouse_hint_shown = self.mouse_hint_shown;
+        fresh.mouse_screen_time = self.mouse_screen_time;
+        fresh.frame_size = self.frame_size;
+        fresh.debug_log = std::mem::take(&mut self.debug_log);
+        *self = fresh;
+    }
+
+    fn game_state(&self) -> GameState {
+        if self.choosing_loadout {
+            return GameState::ChoosingLoadout;
+        }
+        if self.progress.victory {
+            return GameState::Victory;
+        }
+        if self.progress.game_over {
+            return GameState::GameOver;
+        }
+        if self.paused {
+            return GameState::Paused;
+        }
+        if self.ability_list_state.selected().is_some() {
+            return GameState::Abilities;
+        }
+        if let Some(slot) = self.targeting_slot {
+            return GameState::TargetingAbility(slot);
+        }
+        match self.game.improvement_choice_set() {
             Some(set) => {
                 let num_choices = match set.info {
                     ImprovementInfo::ShieldUpgradeInfo(ref vec) => vec.len(),
@@ -421,139 +1904,2356 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>) -> io::Result<()> {
                 GameState::ChoosingImprovement(num_choices)
             }
             None => GameState::Playing,
+        }
+    }
+
+    /// a coaching tip for the status line when nothing else is being said
+    fn coaching_tip(&self) -> Option<&'static str> {
+        if !self.settings.coaching || !matches!(self.game_state(), GameState::Playing) {
+            return None;
+        }
+        let being = &self.game.player().being;
+        let critical = being.hit_points * 4 <= being.max_hit_points;
+        // the core knows which chains it would accept, so ask it for an all-potion one
+        let potion_chain = || {
+            self.game.legal_selections().into_iter().any(|chain| {
+                chain.iter().all(
+                    |tp| matches!(self.game.get_tile(tp), Some(t) if t.tile_type == TileType::Potion),
+                )
+            })
         };
-        let cursor_position = match game_state {
-            GameState::Playing => playing_cursor_position,
-            GameState::ChoosingImprovement(_) => choosing_improvement_cursor_position,
+        if critical && potion_chain() {
+            return Some("potions available — consider healing");
+        }
+        let healer_present =
+            self.game
+                .specials()
+                .into_iter()
+                .any(|(_tp, t, _sid)| match t.tile_info {
+                    TileInfo::Special(special) => {
+                        matches!(special.special_type, SpecialType::Healer)
+                    }
+                    _ => false,
+                });
+        if healer_present {
+            return Some("a Healer keeps enemies topped up — take it out first");
+        }
+        None
+    }
+
+    /// a short "-3 HP, +12 coins, 2 enemies slain" line for the turn just resolved
+    fn push_turn_toast(&mut self, summary: &TurnSummary, hit_points_lost: u64) {
+        let mut parts = vec![];
+        if hit_points_lost > 0 {
+            parts.push(format!("-{} HP", hit_points_lost));
+        }
+        if summary.coins_collected > 0 {
+            parts.push(format!("+{} coins", summary.coins_collected));
+        }
+        if summary.enemies_slain > 0 {
+            parts.push(format!(
+                "{} {} slain",
+                summary.enemies_slain,
+                if summary.enemies_slain == 1 {
+                    "enemy"
+                } else {
+                    "enemies"
+                }
+            ));
+        }
+        if parts.is_empty() {
+            return;
+        }
+        self.toasts.push(StatusMessage::new(parts.join(", ")));
+        if self.toasts.len() > MAX_TOASTS {
+            self.toasts.remove(0);
+        }
+    }
+
+    fn notify(&mut self, severity: Severity, text: String) {
+        logging::info(&text);
+        self.set_status_message(text);
+        let notifications = &self.settings.notifications;
+        let muted = match severity {
+            Severity::Info => notifications.mute_info,
+            Severity::Warning => notifications.mute_warning,
+            Severity::Danger => notifications.mute_danger,
         };
+        if muted {
+            return;
+        }
+        match severity {
+            Severity::Info => {}
+            Severity::Warning => {
+                self.flash = Some(Flash {
+                    color: Color::Yellow,
+                    started_at: Instant::now(),
+                })
+            }
+            Severity::Danger => {
+                self.ring_bell = true;
+                self.flash = Some(Flash {
+                    color: Color::Red,
+                    started_at: Instant::now(),
+                });
+            }
+        }
+    }
 
-        terminal.draw(|f| {
-            ui(
-                f,
-                &game,
-                cursor_position,
-                &improvement_choice_selection_positions,
-            )
-        })?;
-
-        if let Event::Key(key) = event::read()? {
-            match game.improvement_choice_set() {
-                Some(set) => {
-                    // choosing improvement
-                    match key.code {
-                        KeyCode::Char('q') => return Ok(()),
-                        KeyCode::Char(' ') => {
-                            let cursor_pos = terminal.get_cursor()?;
-                            let index_pressed =
-                                improvement_choice_index_from_cursor_position(cursor_pos);
-                            let mut removed = false;
-                            for (vec_idx, pressed_idx) in
-                                improvement_choice_indeces.iter().enumerate()
-                            {
-                                if *pressed_idx == index_pressed {
-                                    improvement_choice_indeces.remove(vec_idx);
-                                    improvement_choice_selection_positions.remove(vec_idx);
-                                    removed = true;
-                                    break;
-                                }
-                            }
-                            if !removed {
-                                improvement_choice_indeces.push(index_pressed);
-                                improvement_choice_selection_positions.push(cursor_pos);
-                            }
-                            if improvement_choice_indeces.len() == set.num_to_choose {
-                                game.choose_improvements(&improvement_choice_indeces);
-                                improvement_choice_indeces.clear();
-                                improvement_choice_selection_positions.clear();
-                                choosing_improvement_cursor_position = (0, 1);
-                            }
-                        }
-                        KeyCode::Char('j') | KeyCode::Down => {
-                            choosing_improvement_cursor_position =
-                                move_cursor(terminal, CursorMove::Down, game_state)?
-                        }
-                        KeyCode::Char('k') | KeyCode::Up => {
-                            choosing_improvement_cursor_position =
-                                move_cursor(terminal, CursorMove::Up, game_state)?
-                        }
-                        _ => {}
+    fn set_status_message(&mut self, text: String) {
+        self.status_message = Some(StatusMessage::new(text));
+    }
+
+    fn expire_notifications(&mut self) {
+        if let Some(ref msg) = self.status_message {
+            if Instant::now() >= msg.expires_at {
+                self.status_message = None;
+            }
+        }
+        self.toasts
+            .retain(|toast| Instant::now() < toast.expires_at);
+    }
+
+    /// keeps the cursors inside what they point at once the terminal changes size
+    fn handle_resize(&mut self, width: u16, height: u16) {
+        // mouse math uses this before the next draw updates it
+        self.frame_size = Rect::new(0, 0, width, height);
+        self.last_mouse_tile = None;
+        let board = self.board();
+        let tp = tile_position_from_cursor_position(self.playing_cursor_position, board);
+        self.playing_cursor_position = cursor_position_from_tile_position(
+            &TilePosition::new(
+                tp.y.clamp(0, board.height as isize - 1),
+                tp.x.clamp(0, board.width as isize - 1),
+            ),
+            board,
+        );
+        if let GameState::ChoosingImprovement(num_choices) = self.game_state() {
+            if let Some(index) = self.improvement_list_state.selected() {
+                self.improvement_list_state
+                    .select(Some(index.min(num_choices.saturating_sub(1))));
+            }
+        }
+    }
+
+    fn open_menu(&mut self, menu: Menu) {
+        self.menu = Some(menu);
+        self.menu_state.select(Some(0));
+    }
+
+    fn handle_loadout_key(&mut self, code: KeyCode) {
+        let ability_types = self.game.available_ability_types();
+        let max_slots = self.game.player().abilities.len();
+        match self.args.keybindings.list_action(code) {
+            Some(Action::MoveDown) => move_list_selection(
+                &mut self.loadout_list_state,
+                CursorMove::Down,
+                ability_types.len(),
+            ),
+            Some(Action::MoveUp) => move_list_selection(
+                &mut self.loadout_list_state,
+                CursorMove::Up,
+                ability_types.len(),
+            ),
+            Some(Action::ToggleChoice) => {
+                let index = self.loadout_list_state.selected().unwrap_or(0);
+                match self
+                    .loadout_choice_indeces
+                    .iter()
+                    .position(|idx| *idx == index)
+                {
+                    Some(vec_idx) => {
+                        self.loadout_choice_indeces.remove(vec_idx);
                     }
+                    None if self.loadout_choice_indeces.len() < max_slots => {
+                        self.loadout_choice_indeces.push(index)
+                    }
+                    None => self.set_status_message(format!("only {} slots", max_slots)),
+                };
+            }
+            // picks fill the slots in order, slots left over keep their default
+            Some(Action::ConfirmChoices) => {
+                for (slot, idx) in self.loadout_choice_indeces.iter().enumerate() {
+                    let (ability_type, _) = ability_types[*idx];
+                    self.game.set_ability(slot, ability_type);
                 }
-                None => {
-                    // playing on board
-                    match key.code {
-                        KeyCode::Char('q') => return Ok(()),
-                        KeyCode::Char(' ') => {
-                            if game.drop_selection() {
-                                // slashed tiles; have enemies attack and then pull down tiles,
-                                // randomizing the new ones
-                                game.apply_incoming_damage();
-                                game.apply_gravity_and_randomize_new_tiles();
-                                game.run_end_of_turn_on_specials();
-                            }
-                        }
-                        KeyCode::Char('x') => {
-                            game.select_tile(&tile_position_from_cursor_position(
-                                terminal.get_cursor()?,
-                            ));
-                        }
-                        KeyCode::Char('h') | KeyCode::Left => {
-                            playing_cursor_position =
-                                move_cursor(terminal, CursorMove::Left, game_state)?
-                        }
-                        KeyCode::Char('j') | KeyCode::Down => {
-                            playing_cursor_position =
-                                move_cursor(terminal, CursorMove::Down, game_state)?
-                        }
-                        KeyCode::Char('k') | KeyCode::Up => {
-                            playing_cursor_position =
-                                move_cursor(terminal, CursorMove::Up, game_state)?
-                        }
-                        KeyCode::Char('l') | KeyCode::Right => {
-                            playing_cursor_position =
-                                move_cursor(terminal, CursorMove::Right, game_state)?
-                        }
-                        KeyCode::Char('1') => {
-                            game.cast_ability(0);
-                        }
-                        KeyCode::Char('2') => {
-                            game.cast_ability(1);
-                        }
-                        KeyCode::Char('3') => {
-                            game.cast_ability(2);
-                        }
-                        KeyCode::Char('4') => {
-                            game.cast_ability(3);
-                        }
-                        _ => {}
-                    };
+                self.level = self.game.player().level();
+                self.choosing_loadout = false;
+            }
+            Some(Action::Cancel) => self.loadout_choice_indeces.clear(),
+            _ => {
+                if code == KeyCode::Char('r') {
+                    let mut indeces: Vec<usize> = (0..ability_types.len()).collect();
+                    // from the run's seed, so a replay deals the same loadout
+                    self.loadout_shuffles += 1;
+                    let mut rng =
+                        StdRng::seed_from_u64(self.game.seed().wrapping_add(self.loadout_shuffles));
+                    indeces.shuffle(&mut rng);
+                    indeces.truncate(max_slots);
+                    self.loadout_choice_indeces = indeces;
                 }
             }
         }
     }
-}
 
-fn ui<B: Backend>(
-    f: &mut Frame<B>,
-    game: &Game,
-    cursor_pos: (u16, u16),
-    improvement_choice_selection_positions: &Vec<(u16, u16)>,
-) {
-    let game_widget = GameWidget {
-        game: game,
-        cursor_pos,
-        improvement_choice_selection_positions,
-    };
+    fn handle_abilities_key(&mut self, code: KeyCode) {
+        let num_slots = self.game.player().abilities.len();
+        let slot = self.ability_list_state.selected().unwrap_or(0);
+        let confirming_drop = self.confirming_ability_drop;
+        self.confirming_ability_drop = false;
+        match self.args.keybindings.list_action(code) {
+            Some(Action::Cancel) => {
+                self.ability_list_state.select(None);
+                return;
+            }
+            Some(Action::MoveDown) => {
+                move_list_selection(&mut self.ability_list_state, CursorMove::Down, num_slots);
+                return;
+            }
+            Some(Action::MoveUp) => {
+                move_list_selection(&mut self.ability_list_state, CursorMove::Up, num_slots);
+                return;
+            }
+            _ => {}
+        }
+        match code {
+            KeyCode::Char('A') => self.ability_list_state.select(None),
+            // move the selected ability a slot down or up
+            KeyCode::Char('J') if slot + 1 < num_slots => {
+                self.game.swap_ability_slots(slot, slot + 1);
+                self.ability_list_state.select(Some(slot + 1));
+            }
+            KeyCode::Char('K') if slot > 0 => {
+                self.game.swap_ability_slots(slot, slot - 1);
+                self.ability_list_state.select(Some(slot - 1));
+            }
+            KeyCode::Char('d') => {
+                let name = match self.game.player().abilities.get(slot) {
+                    Some(Some(a)) => a.ability_type.name_description().0,
+                    _ => return,
+                };
+                if confirming_drop {
+                    self.game.drop_ability(slot);
+                    self.set_status_message(format!("dropped {}", name));
+                } else {
+                    self.confirming_ability_drop = true;
+                    self.set_status_message(format!("press d again to drop {}", name));
+                }
+            }
+            _ => {}
+        }
+    }
 
-    f.render_widget(
-        game_widget,
-        Rect::new(
-            PLAYING_CURSOR_MAX_LEFT,
-            PLAYING_CURSOR_MAX_UP,
-            PLAYING_CURSOR_MAX_RIGHT - PLAYING_CURSOR_MAX_LEFT,
-            PLAYING_CURSOR_MAX_DOWN - PLAYING_CURSOR_MAX_UP,
-        ),
-    );
+    fn log_debug(&mut self, msg: String) {
+        logging::debug(&msg);
+        self.debug_log.push(msg);
+        if self.debug_log.len() > DEBUG_LOG_LINES {
+            self.debug_log.remove(0);
+        }
+    }
+
+    fn handle_command_key(&mut self, code: KeyCode) {
+        match code {
+            KeyCode::Esc => self.command_input = None,
+            KeyCode::Enter => {
+                if let Some(input) = self.command_input.take() {
+                    self.run_command(&input);
+                }
+            }
+            KeyCode::Backspace => {
+                if let Some(input) = self.command_input.as_mut() {
+                    input.pop();
+                }
+            }
+            KeyCode::Char(c) => {
+                if let Some(input) = self.command_input.as_mut() {
+                    input.push(c);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn run_command(&mut self, input: &str) {
+        let command = match Command::parse(input, self.debug) {
+            Ok(command) => command,
+            Err(err) => {
+                self.set_status_message(err);
+                return;
+            }
+        };
+        let tp = match command {
+            Command::Goto(tp) | Command::SetTile(tp, _) => tp,
+        };
+        let board = self.board();
+        if !board.contains(&tp) {
+            self.set_status_message(format!(
+                "({}, {}) is off the {}x{} board",
+                tp.y, tp.x, board.height, board.width
+            ));
+            return;
+        }
+        match command {
+            Command::Goto(tp) => {
+                self.playing_cursor_position =
+                    cursor_position_from_tile_position(&tp, self.board());
+            }
+            Command::SetTile(tp, tile_type) => {
+                self.game.set_tile(&tp, Tile::new(tile_type));
+                self.hints.clear();
+                self.log_debug(format!(":{}", input.trim()));
+            }
+        }
+    }
+
+    /// false while playing or verifying a replay, which runs under its own pinned settings
+    /// and shouldn't touch the player's settings, stats, achievements or save
+    fn persists(&self) -> bool {
+        self.args.replay.is_none() && self.args.verify_replay.is_none()
+    }
+
+    /// the time double presses are measured against
+    fn now(&self) -> Instant {
+        self.key_clock.unwrap_or_else(Instant::now)
+    }
+
+    fn save_settings(&self) {
+        if self.persists() {
+            self.settings.save();
+        }
+    }
+
+    /// the same seed only plays out the same with the same difficulty, mode, gravity, board and
+    /// selection rules
+    fn seed_best_key(&self) -> String {
+        format!(
+            "{} {} {} {} {}x{} min{}{}",
+            self.game.seed(),
+            difficulty_name(self.game.difficulty()),
+            self.mode.name(),
+            gravity_name(self.gravity),
+            self.game.board_width(),
+            self.game.board_height(),
+            self.game.min_selection_length(),
+            if self.settings.four_way_selection {
+                " four-way"
+            } else {
+                ""
+            }
+        )
+    }
+
+    fn record_run(&mut self) {
+        if self.persists() {
+            let score = self.game.score();
+            let key = self.seed_best_key();
+            // compared before this run is stored; a very first run has nothing to beat
+            self.previous_seed_best = self.seed_bests.best(&key);
+            self.new_best =
+                if self.lifetime_stats.games_played > 0 && score > self.lifetime_stats.best_score {
+                    Some("NEW BEST SCORE!")
+                } else if self.previous_seed_best.map_or(false, |best| score > best) {
+                    Some("NEW BEST ON THIS SEED!")
+                } else {
+                    None
+                };
+            self.lifetime_stats.record_run(&self.game);
+            self.seed_bests.record(key, score);
+        }
+    }
+
+    fn save_game(&mut self) {
+        if !self.persists() {
+            self.set_status_message(String::from("saving is off during a replay"));
+            return;
+        }
+        let result = self
+            .game
+            .to_json()
+            .map_err(|e| e.to_string())
+            .and_then(|game| {
+                toml::to_string(&SavedRun {
+                    game,
+                    progress: self.progress,
+                })
+                .map_err(|e| e.to_string())
+            })
+            .and_then(|contents| persist::write_atomically(SAVE_FILE, &contents));
+        match result {
+            Ok(()) => {
+                self.dirty_since_save = false;
+                self.owns_save = true;
+                self.set_status_message(String::from("game saved"));
+            }
+            Err(e) => {
+                logging::info(&format!("failed to save {}: {}", SAVE_FILE, e));
+                self.set_status_message(String::from("failed to save, see log.txt"));
+            }
+        }
+    }
+
+    /// quitting only needs confirming when a turn would be lost
+    fn has_unsaved_progress(&self) -> bool {
+        self.dirty_since_save && !self.progress.game_over && !self.progress.victory
+    }
+
+    fn toggle_improvement_choice(&mut self, index: usize) {
+        let num_to_choose = match self.game.improvement_choice_set() {
+            Some(set) => set.num_to_choose,
+            None => return,
+        };
+        match self
+            .improvement_choice_indeces
+            .iter()
+            .position(|idx| *idx == index)
+        {
+            Some(vec_idx) => {
+                self.improvement_choice_indeces.remove(vec_idx);
+            }
+            // one more than the core takes would only be refused when committing
+            None if self.improvement_choice_indeces.len() >= num_to_choose => {
+                self.set_status_message(format!(
+                    "only {} can be chosen, unselect one first",
+                    num_to_choose
+                ));
+                return;
+            }
+            None => self.improvement_choice_indeces.push(index),
+        };
+        self.confirming_improvements = false;
+        if self.improvement_choice_indeces.len() == num_to_choose
+            && self.settings.improvement_commit == ImprovementCommit::Instant
+        {
+            self.commit_improvements();
+        }
+    }
+
+    /// Enter on the improvement screen; commits or asks for a second Enter per the setting
+    fn confirm_improvements(&mut self) {
+        let num_to_choose = match self.game.improvement_choice_set() {
+            Some(set) => set.num_to_choose,
+            None => return,
+        };
+        if self.improvement_choice_indeces.len() != num_to_choose {
+            self.set_status_message(format!("choose {} first", num_to_choose));
+            return;
+        }
+        match self.settings.improvement_commit {
+            ImprovementCommit::EnterWithPreview if !self.confirming_improvements => {
+                self.confirming_improvements = true;
+                self.set_status_message(String::from("check the preview, Enter again to confirm"));
+            }
+            _ => self.commit_improvements(),
+        }
+    }
+
+    /// Cancel on the improvement screen; backs out of the preview, or clears the choices
+    fn cancel_improvement_choices(&mut self) {
+        if self.confirming_improvements {
+            self.confirming_improvements = false;
+        } else {
+            self.improvement_choice_indeces.clear();
+        }
+    }
+
+    fn announce_level_up(&mut self) {
+        let level = self.game.player().level();
+        if level > self.level {
+            self.notify(Severity::Info, format!("Level {} reached!", level));
+        }
+        self.level = level;
+    }
+
+    fn commit_improvements(&mut self) {
+        let ability_names = |game: &Game| -> Vec<Option<&'static str>> {
+            game.player()
+                .abilities
+                .iter()
+                .map(|a| a.as_ref().map(|a| a.ability_type.name_description().0))
+                .collect()
+        };
+        let abilities_before = ability_names(&self.game);
+        let purchase = self.game.improvement_choice_set().map_or(false, |set| {
+            matches!(set.info, ImprovementInfo::CoinPurchaseInfo(_))
+        });
+        let coin_cents_before = self.game.player().coin_cents;
+        self.game
+            .choose_improvements(&self.improvement_choice_indeces);
+        // confirms the coins actually left the purse
+        if purchase {
+            let coin_cents_after = self.game.player().coin_cents;
+            self.set_status_message(format!(
+                "spent {} coins, {} left",
+                self.settings
+                    .number_format
+                    .format(coin_cents_before.saturating_sub(coin_cents_after) as u64),
+                self.settings.number_format.format(coin_cents_after as u64)
+            ));
+        }
+        // slots that gained or swapped an ability stay marked until the next turn
+        for (slot, name) in ability_names(&self.game).into_iter().enumerate() {
+            if let Some(name) = name {
+                if abilities_before.get(slot) != Some(&Some(name)) {
+                    self.new_ability_slots.push(slot);
+                    self.set_status_message(format!("slot {}: {}", slot + 1, name));
+                }
+            }
+        }
+        self.improvement_choice_indeces.clear();
+        self.improvement_list_state.select(Some(0));
+        self.confirming_improvements = false;
+        self.announce_level_up();
+    }
+
+    fn jump_to_nearest(&mut self, target: JumpTarget, previous: Option<JumpCycle>) {
+        let (anchor, index) = match previous {
+            Some(cycle) if cycle.target == target => (cycle.anchor, cycle.index + 1),
+            _ => (
+                tile_position_from_cursor_position(self.playing_cursor_position, self.board()),
+                0,
+            ),
+        };
+        let mut targets: Vec<TilePosition> = self
+            .board()
+            .tile_positions()
+            .into_iter()
+            .filter(|tp| match self.game.get_tile(tp) {
+                Some(t) => target.matches(t.tile_type, &self.theme),
+                None => false,
+            })
+            .collect();
+        if targets.is_empty() {
+            self.set_status_message(match target {
+                JumpTarget::Enemy => String::from("no enemies on the board"),
+                JumpTarget::Glyph(c) => format!("no '{}' tiles on the board", c),
+            });
+            return;
+        }
+        targets.sort_by_key(|tp| manhattan_distance(&anchor, tp));
+        let index = index % targets.len();
+        self.playing_cursor_position =
+            cursor_position_from_tile_position(&targets[index], self.board());
+        self.jump_cycle = Some(JumpCycle {
+            target,
+            anchor,
+            index,
+        });
+    }
+
+    /// selects the last dropped chain's shape again, starting at the cursor
+    fn repeat_last_selection(&mut self) {
+        if self.last_selection_offsets.is_empty() {
+            self.set_status_message(String::from("no chain to repeat yet"));
+            return;
+        }
+        if self.game.get_selection_start().is_some() {
+            self.set_status_message(String::from("drop or finish the current selection first"));
+            return;
+        }
+        let mut tp = tile_position_from_cursor_position(self.playing_cursor_position, self.board());
+        let mut positions = vec![TilePosition::new(tp.y, tp.x)];
+        for (dy, dx) in self.last_selection_offsets.iter() {
+            tp = TilePosition::new(tp.y + dy, tp.x + dx);
+            positions.push(TilePosition::new(tp.y, tp.x));
+        }
+        for (step, position) in positions.iter().enumerate() {
+            self.game.select_tile(position);
+            if selected_tiles(&self.game).len() != step + 1 {
+                // a partial chain isn't what was asked for, so don't leave it behind
+                for _ in 0..selected_tiles(&self.game).len() {
+                    self.game.unselect_last_tile();
+                }
+                self.set_status_message(format!(
+                    "pattern doesn't fit here, it broke at tile {} of {}",
+                    step + 1,
+                    positions.len()
+                ));
+                return;
+            }
+        }
+    }
+
+    /// greedily extends the selection from its end along the only matching neighbor,
+    /// stopping at a dead end or the first branch
+    fn quick_extend_selection(&mut self) {
+        let mut end = match selected_tiles(&self.game).last() {
+            Some(&(end, _)) => end,
+            None => return,
+        };
+        let start_len = selected_tiles(&self.game).len();
+        let mut selected_len = start_len;
+        loop {
+            // the core decides what may join the chain, including which types mix
+            let candidates = self.game.selectable_neighbors();
+            if candidates.len() != 1 {
+                break;
+            }
+            self.game.select_tile(&candidates[0]);
+            let now_selected_len = selected_tiles(&self.game).len();
+            if now_selected_len <= selected_len {
+                break;
+            }
+            selected_len = now_selected_len;
+            end = candidates[0];
+        }
+        self.playing_cursor_position = cursor_position_from_tile_position(&end, self.board());
+        self.set_status_message(format!("grabbed {} more", selected_len - start_len));
+    }
+
+    /// a second press on the same tile within DOUBLE_PRESS_WINDOW grabs the rest of the chain
+    fn select_under_cursor(&mut self) {
+        self.hints.clear();
+        let tp = tile_position_from_cursor_position(self.playing_cursor_position, self.board());
+        match self.last_select_press {
+            Some((at, last_tp))
+                if last_tp == tp && self.now().duration_since(at) <= DOUBLE_PRESS_WINDOW =>
+            {
+                self.last_select_press = None;
+                self.quick_extend_selection();
+            }
+            _ => {
+                self.last_select_press = Some((self.now(), tp));
+                self.game.select_tile(&tp);
+            }
+        }
+    }
+
+    fn move_playing_cursor(&mut self, m: CursorMove) {
+        self.playing_cursor_position = move_cursor(self.playing_cursor_position, m, self.board());
+    }
+
+    fn cast_ability(&mut self, slot: usize) {
+        match self.game.cast_ability(slot) {
+            // an ability like Reshuffle can rewrite the board and drop the selection
+            CastResult::Cast => {
+                self.hints.clear();
+                self.last_select_press = None;
+            }
+            // nothing is spent until a tile is picked, see target_ability
+            CastResult::NeedsTarget => {
+                self.targeting_slot = Some(slot);
+                self.set_status_message(format!(
+                    "pick a tile for the ability, {} or right-click to cancel",
+                    self.args.keybindings.describe(Action::Cancel)
+                ));
+            }
+            CastResult::OnCooldown(remaining) => self.notify(
+                Severity::Warning,
+                format!(
+                    "ability {} is cooling down, {} turns left",
+                    slot + 1,
+                    remaining
+                ),
+            ),
+            CastResult::Passive => self.notify(
+                Severity::Warning,
+                format!("ability {} is passive, it works while equipped", slot + 1),
+            ),
+            CastResult::EmptySlot => self.notify(
+                Severity::Warning,
+                format!("no ability in slot {}", slot + 1),
+            ),
+        }
+    }
 
-    f.set_cursor(cursor_pos.0, cursor_pos.1);
+    fn target_ability(&mut self, tp: TilePosition) {
+        if let Some(slot) = self.targeting_slot.take() {
+            self.hints.clear();
+            self.game.apply_targeted_ability(slot, &tp);
+            self.status_message = None;
+        }
+    }
+
+    fn cancel_targeting(&mut self) {
+        if self.targeting_slot.take().is_some() {
+            self.set_status_message(String::from("cast cancelled"));
+        }
+    }
+
+    /// a left click on the board picks the target, a right click anywhere cancels the cast
+    fn handle_targeting_mouse(&mut self, mouse: MouseEvent) {
+        let board = self.board();
+        let on_board = mouse.column >= PLAYING_CURSOR_MAX_LEFT
+            && mouse.column <= board.max_right()
+            && mouse.row >= PLAYING_CURSOR_MAX_UP
+            && mouse.row <= board.max_down();
+        match mouse.kind {
+            MouseEventKind::Down(MouseButton::Left) if on_board => {
+                let tp = tile_position_from_cursor_position((mouse.column, mouse.row), board);
+                self.playing_cursor_position = cursor_position_from_tile_position(&tp, board);
+                self.target_ability(tp);
+            }
+            MouseEventKind::Down(MouseButton::Right) => self.cancel_targeting(),
+            _ => {}
+        }
+    }
+
+    /// shows the best suggested move, or the next-best on repeated presses
+    fn cycle_hint(&mut self) {
+        if self.hints.is_empty() {
+            self.hints = self.game.suggest_moves(HINT_CANDIDATES);
+            self.hint_index = 0;
+        } else {
+            self.hint_index = (self.hint_index + 1) % self.hints.len();
+        }
+        match self.hints.get(self.hint_index) {
+            Some(hint) => self.set_status_message(format!(
+                "hint {}/{}: score {} (n for next)",
+                self.hint_index + 1,
+                self.hints.len(),
+                hint.score
+            )),
+            None => self.set_status_message(String::from("no moves to suggest")),
+        }
+    }
+
+    /// drops the selection, first asking for a second Space when the HP swing is large
+    fn request_drop(&mut self, confirmed: bool) {
+        if let (Some(threshold), false) = (self.settings.big_swing_threshold, confirmed) {
+            if let Some(outcome) = self.game.preview_drop_selection() {
+                let swing = outcome.hit_points_gained as i64 - outcome.incoming_damage as i64;
+                if swing.unsigned_abs() >= threshold as u64 {
+                    self.confirming_big_swing = true;
+                    self.set_status_message(format!(
+                        "this drop changes HP by {:+}, drop again to confirm",
+                        swing
+                    ));
+                    return;
+                }
+            }
+        }
+        self.drop_selection();
+    }
+
+    fn drop_selection(&mut self) {
+        let selected = selected_tiles(&self.game);
+        let before = (self.game.snapshot(), self.progress);
+        let totals = StatTotals::of(&self.game);
+        if self.debug {
+            // resolved one combat event per keypress, see step_combat
+            if self.game.begin_stepped_drop() {
+                self.undo = Some(before);
+                self.stepped_drop = Some((selected, totals));
+                self.set_status_message(String::from("stepping combat, any key for next step"));
+            }
+            return;
+        }
+        let animate = self.settings.animate_gravity && !self.settings.reduced_motion;
+        let dropped = if animate {
+            self.game.drop_selection_unsettled()
+        } else {
+            self.game.drop_selection()
+        };
+        if !dropped {
+            // the core keeps a chain that's too short selected
+            let missing = self
+                .game
+                .min_selection_length()
+                .saturating_sub(selected.len());
+            if missing > 0 {
+                self.set_status_message(format!("chain too short, need {} more", missing));
+            }
+            return;
+        }
+        self.undo = Some(before);
+        if animate {
+            // the turn is settled against the board the chain left behind; only the fall
+            // itself is animated
+            self.settling_drop = Some(self.resolve_drop(&selected, totals));
+            return;
+        }
+        self.finish_drop(selected, totals);
+    }
+
+    /// puts the game back to just before the last drop, with its chain still selected
+    fn undo_last_turn(&mut self) {
+        match self.undo.take() {
+            Some((snapshot, progress)) => {
+                self.game.restore(&snapshot);
+                self.progress = progress;
+                // what's announced as it changes has to match the restored game again
+                self.level = self.game.player().level();
+                let boss = boss_status(&self.game);
+                self.boss_present = boss.is_some();
+                self.boss_enraged = boss.map_or(false, |boss| boss.enraged);
+                self.specials_on_board = self.game.specials().len();
+                self.spawn_ramp_level = self.game.spawn_weights().ramp_level;
+                self.improvement_choice_indeces.clear();
+                self.hints.clear();
+                self.new_ability_slots.clear();
+                self.dirty_since_save = true;
+                self.set_status_message(String::from("undid the last turn"));
+            }
+            None => self.set_status_message(String::from("nothing to undo")),
+        }
+    }
+
+    /// lets the falling tiles drop one more row, ending the turn once they've settled
+    fn settle_step(&mut self) {
+        if !self.game.apply_gravity_step() {
+            if let Some(summary) = self.settling_drop.take() {
+                self.end_turn(&summary);
+            }
+        }
+    }
+
+    /// applies the next combat event of a stepped drop, finishing the turn after the last
+    fn step_combat(&mut self) {
+        match self.game.step_resolution() {
+            Some(event) => {
+                let description = event.description();
+                self.set_status_message(description.clone());
+                self.log_debug(description);
+            }
+            None => {
+                if let Some((selected, totals)) = self.stepped_drop.take() {
+                    self.finish_drop(selected, totals);
+                }
+            }
+        }
+    }
+
+    /// everything after the selected tiles have been slashed; the core's turn order is
+    /// select_tile* -> drop_selection -> apply_incoming_damage ->
+    /// apply_gravity_and_randomize_new_tiles -> run_end_of_turn_on_specials, with the damage
+    /// in resolve_drop and the rest in end_turn
+    fn finish_drop(&mut self, selected: Vec<(TilePosition, TileType)>, totals: StatTotals) {
+        let summary = self.resolve_drop(&selected, totals);
+        self.end_turn(&summary);
+    }
+
+    /// what the slashed chain did and the enemies' attack, both before any tile moves
+    fn resolve_drop(
+        &mut self,
+        selected: &[(TilePosition, TileType)],
+        totals: StatTotals,
+    ) -> TurnSummary {
+        self.new_ability_slots.clear();
+        // the board changed, so old suggestions no longer apply
+        self.hints.clear();
+        let summary = TurnSummary::from_drop(selected, totals, &self.game);
+        self.last_selection_offsets = selected
+            .windows(2)
+            .map(|pair| (pair[1].0.y - pair[0].0.y, pair[1].0.x - pair[0].0.x))
+            .collect();
+        // slashed tiles; have enemies attack from where they stand
+        let shields_before = self.game.player().being.shields as u64;
+        let damage_report = self.game.apply_incoming_damage();
+        if damage_report.shields_lost > 0 || damage_report.hit_points_lost > 0 {
+            self.set_status_message(format!(
+                "took {} to shields, {} to HP",
+                damage_report.shields_lost, damage_report.hit_points_lost
+            ));
+        }
+        self.push_turn_toast(&summary, damage_report.hit_points_lost as u64);
+        let being = &self.game.player().being;
+        let (hit_points, max_hit_points) = (being.hit_points, being.max_hit_points);
+        if damage_report.hit_points_lost > 0 && hit_points > 0 && hit_points * 4 <= max_hit_points {
+            self.notify(
+                Severity::Danger,
+                format!("HP critical: {}/{}", hit_points, max_hit_points),
+            );
+        }
+        if damage_report.shields_lost > 0 && !self.settings.reduced_motion {
+            self.shield_drain = Some(ShieldDrain {
+                from: shields_before,
+                started_at: Instant::now(),
+            });
+        }
+        summary
+    }
+
+    /// pulls down tiles, randomizing the new ones, and everything that comes with a new turn
+    fn end_turn(&mut self, summary: &TurnSummary) {
+        self.game.apply_gravity_and_randomize_new_tiles();
+        self.game.run_end_of_turn_on_specials();
+        self.dirty_since_save = true;
+        self.progress.record_turn(summary);
+        // the core spawns the boss after a milestone and queues its reward improvements
+        let boss = boss_status(&self.game);
+        let boss_present = boss.is_some();
+        let boss_enraged = boss.map_or(false, |boss| boss.enraged);
+        if boss_present && !self.boss_present {
+            self.notify(Severity::Warning, String::from("A boss has appeared!"));
+        } else if !boss_present && self.boss_present {
+            self.set_status_message(String::from("Boss defeated!"));
+        }
+        if boss_enraged && !self.boss_enraged {
+            self.notify(
+                Severity::Warning,
+                String::from("The boss is enraged, its attacks now hit twice as hard!"),
+            );
+        }
+        self.boss_present = boss_present;
+        self.boss_enraged = boss_enraged;
+        // several can be out at once, each acting on its own cooldown
+        let specials_on_board = self.game.specials().len();
+        if specials_on_board > self.specials_on_board && specials_on_board > 1 {
+            self.notify(
+                Severity::Warning,
+                format!("{} specials on the board", specials_on_board),
+            );
+        }
+        self.specials_on_board = specials_on_board;
+        let spawn_ramp_level = self.game.spawn_weights().ramp_level;
+        if spawn_ramp_level > self.spawn_ramp_level {
+            self.set_status_message(String::from("enemies are spawning more often"));
+        }
+        self.spawn_ramp_level = spawn_ramp_level;
+        self.announce_level_up();
+        if self.mode.objective_complete(&self.progress) {
+            self.progress.victory = true;
+            self.record_run();
+        } else if self.mode.objective_failed(&self.progress, &self.game) && !self.progress.game_over
+        {
+            self.set_status_message(String::from("out of turns, objective failed"));
+            self.progress.game_over = true;
+            self.record_run();
+        }
+        if self.game.is_game_over() && !self.progress.game_over && !self.progress.victory {
+            self.progress.game_over = true;
+            self.record_run();
+        }
+        // a finished run shouldn't come back at the next startup, but a save from some
+        // other run is left alone
+        if (self.progress.game_over || self.progress.victory) && self.owns_save {
+            let _ = std::fs::remove_file(SAVE_FILE);
+        }
+        if !self.persists() {
+            return;
+        }
+        for a in self.achievements.evaluate(&self.progress, &self.game) {
+            self.set_status_message(format!(
+                "Achievement unlocked! {} - {}",
+                a.name, a.description
+            ));
+        }
+    }
+}
+
+/// dims everything already drawn in its area so an overlay on top stands out
+struct Dim;
+impl Widget for Dim {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        buf.set_style(area, Style::default().add_modifier(Modifier::DIM));
+    }
+}
+
+fn run_app<B: Backend>(
+    terminal: &mut Terminal<B>,
+    args: Args,
+    mouse_captured: bool,
+) -> io::Result<()> {
+    let mut app = App::new(&args);
+    if args.no_mouse || args.record.is_some() {
+        // no mouse is expected, so there is nothing to hint about
+        app.mouse_hint_shown = true;
+    } else if !mouse_captured {
+        app.mouse_hint_shown = true;
+        app.set_status_message(MOUSE_HINT.to_string());
+    }
+    let mut recording = args.record.as_ref().map(|_| {
+        // a restart mid-recording has to come back to the same board
+        app.args.seed = Some(app.game.seed());
+        Replay {
+            seed: app.game.seed(),
+            mode: app.mode,
+            difficulty: app.game.difficulty(),
+            gravity: app.gravity,
+            improvement_commit: app.settings.improvement_commit,
+            four_way_selection: app.settings.four_way_selection,
+            min_selection_length: app.settings.min_selection_length,
+            big_swing_threshold: app.settings.big_swing_threshold,
+            debug: app.args.debug,
+            keybindings: app.args.keybindings.clone(),
+            keys: vec![],
+            checks: vec![],
+        }
+    });
+    let mut recorded_turn = app.game.turn() as u64;
+    let mut last_key_at = Instant::now();
+    terminal.show_cursor()?;
+    let mut last_tick = Instant::now();
+    loop {
+        app.expire_notifications();
+        let game_state = app.game_state();
+        if app.ring_bell {
+            app.ring_bell = false;
+            let mut stdout = io::stdout();
+            stdout.write_all(b"\x07")?;
+            stdout.flush()?;
+        }
+
+        let elapsed = last_tick.elapsed();
+        last_tick = Instant::now();
+        let mouse_screen =
+            app.menu.is_some() || matches!(game_state, GameState::ChoosingImprovement(_));
+        if mouse_screen && !app.mouse_event_seen && !app.mouse_hint_shown {
+            app.mouse_screen_time += elapsed;
+            if app.mouse_screen_time >= MOUSE_HINT_AFTER {
+                app.mouse_hint_shown = true;
+                app.set_status_message(MOUSE_HINT.to_string());
+            }
+        }
+
+        // transiently zero while resizing; wait for a usable size instead of drawing
+        let size = terminal.size().unwrap_or_default();
+        if size.width > 0 && size.height > 0 {
+            terminal.draw(|f| ui(f, &mut app))?;
+        }
+
+        // input waits until the tiles have landed
+        if app.settling_drop.is_some() {
+            std::thread::sleep(GRAVITY_FRAME);
+            app.settle_step();
+            continue;
+        }
+
+        if !event::poll(TICK_RATE)? {
+            continue;
+        }
+        let ev = event::read()?;
+        if let Event::Resize(width, height) = ev {
+            app.handle_resize(width, height);
+            // repaint everything instead of diffing against the old size
+            terminal.clear()?;
+            continue;
+        }
+        if let Event::Mouse(mouse) = ev {
+            app.mouse_event_seen = true;
+            // mouse only drives the list-based screens
+            let list_move = match mouse.kind {
+                MouseEventKind::ScrollUp => Some(CursorMove::Up),
+                MouseEventKind::ScrollDown => Some(CursorMove::Down),
+                _ => None,
+            };
+            let left_click = mouse.kind == MouseEventKind::Down(MouseButton::Left);
+            if app.paused {
+                // a stray click shouldn't change anything under the banner
+            } else if app.showing_help {
+                // a click dismisses it like any key
+                if left_click {
+                    app.showing_help = false;
+                }
+            } else if let Some(m) = app.menu {
+                let num_items = menu_len(m, &app);
+                if let Some(list_move) = list_move {
+                    move_list_selection(&mut app.menu_state, list_move, num_items);
+                } else if left_click {
+                    let area = menu_area(num_items, app.frame_size);
+                    if mouse.row > area.y && ((mouse.row - area.y - 1) as usize) < num_items {
+                        app.menu_state
+                            .select(Some((mouse.row - area.y - 1) as usize));
+                    }
+                }
+            } else if let GameState::ChoosingImprovement(num_choices) = game_state {
+                if let Some(list_move) = list_move {
+                    move_list_selection(&mut app.improvement_list_state, list_move, num_choices);
+                } else if left_click {
+                    // the list is drawn from the row below the header, scrolled by the offset
+                    if mouse.row > 0 && mouse.row <= app.board().max_down() {
+                        let index = app.improvement_scroll_offset + (mouse.row - 1) as usize;
+                        if index < num_choices {
+                            app.improvement_list_state.select(Some(index));
+                            app.toggle_improvement_choice(index);
+                        }
+                    }
+                }
+            } else if let GameState::TargetingAbility(_) = game_state {
+                app.handle_targeting_mouse(mouse);
+            } else if matches!(game_state, GameState::Playing)
+                && app.command_input.is_none()
+                && app.stepped_drop.is_none()
+            {
+                let board = app.board();
+                let on_board = mouse.column >= PLAYING_CURSOR_MAX_LEFT
+                    && mouse.column <= board.max_right()
+                    && mouse.row >= PLAYING_CURSOR_MAX_UP
+                    && mouse.row <= board.max_down();
+                match mouse.kind {
+                    // clicking or dragging across tiles builds the chain
+                    MouseEventKind::Down(MouseButton::Left)
+                    | MouseEventKind::Drag(MouseButton::Left)
+                        if on_board =>
+                    {
+                        let tp =
+                            tile_position_from_cursor_position((mouse.column, mouse.row), board);
+                        let dragging = mouse.kind == MouseEventKind::Drag(MouseButton::Left);
+                        // a drag reports every cell it crosses, so only act on a new tile
+                        if !(dragging && app.last_mouse_tile == Some(tp)) {
+                            app.hints.clear();
+                            app.game.select_tile(&tp);
+                            app.playing_cursor_position =
+                                cursor_position_from_tile_position(&tp, board);
+                        }
+                        app.last_mouse_tile = Some(tp);
+                        // changing the chain asks again about a big swing
+                        app.confirming_big_swing = false;
+                    }
+                    // a second right-click confirms a big swing, like a second Space
+                    MouseEventKind::Down(MouseButton::Right) => {
+                        let confirming_big_swing = app.confirming_big_swing;
+                        app.confirming_big_swing = false;
+                        app.request_drop(confirming_big_swing);
+                    }
+                    MouseEventKind::Up(MouseButton::Left) => app.last_mouse_tile = None,
+                    _ => {}
+                }
+            }
+            continue;
+        }
+        if let Event::Key(key) = ev {
+            // a key a replay can't write down would leave it out of step, so it does nothing
+            if recording.is_some() && key_name(key.code).is_none() {
+                continue;
+            }
+            if let Some(ref mut recording) = recording {
+                // the previous keys finished a turn, its tiles have landed by now
+                if app.game.turn() as u64 != recorded_turn {
+                    recorded_turn = app.game.turn() as u64;
+                    recording
+                        .checks
+                        .push(Check::new(recording.keys.len(), &app.game));
+                }
+                recording.keys.push((key.code, last_key_at.elapsed()));
+                last_key_at = Instant::now();
+            }
+            if handle_key(&mut app, key.code) {
+                if let Some(ref mut recording) = recording {
+                    if app.game.turn() as u64 != recorded_turn {
+                        recording
+                            .checks
+                            .push(Check::new(recording.keys.len(), &app.game));
+                    }
+                }
+                if let (Some(path), Some(recording)) = (&args.record, &recording) {
+                    if let Err(e) = recording.save(path) {
+                        logging::info(&format!("failed to save recording {}: {}", path, e));
+                    }
+                }
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// the app a recording started from, with its pinned options and settings
+fn replay_start(mut args: Args, replay: &Replay) -> App {
+    args.seed = Some(replay.seed);
+    args.mode = replay.mode;
+    args.difficulty = replay.difficulty;
+    args.gravity = replay.gravity;
+    args.keybindings = replay.keybindings.clone();
+    args.debug = replay.debug;
+    // the rest of settings.toml only changes how things look
+    let mut settings = Settings::load();
+    settings.improvement_commit = replay.improvement_commit;
+    settings.four_way_selection = replay.four_way_selection;
+    settings.min_selection_length = replay.min_selection_length;
+    settings.big_swing_threshold = replay.big_swing_threshold;
+    App::with_settings(&args, settings)
+}
+
+/// plays a recording's keys as fast as they go, comparing the game against each of its
+/// checks; the number of checks on success, the turn of the first mismatch otherwise
+fn verify_replay(args: Args, replay: &Replay) -> Result<usize, u64> {
+    let mut app = replay_start(args, replay);
+    let mut key_clock = Instant::now();
+    let mut checks = replay.checks.iter().peekable();
+    for (idx, (code, gap)) in replay.keys.iter().enumerate() {
+        // keys arrive as far apart as they were recorded, so double presses match
+        key_clock += *gap;
+        app.key_clock = Some(key_clock);
+        while let Some(check) = checks.next_if(|check| check.after_keys <= idx) {
+            if !check.matches(&app.game) {
+                return Err(check.turn);
+            }
+        }
+        if handle_key(&mut app, *code) {
+            break;
+        }
+        while app.settling_drop.is_some() {
+            app.settle_step();
+        }
+    }
+    for check in checks {
+        if !check.matches(&app.game) {
+            return Err(check.turn);
+        }
+    }
+    Ok(replay.checks.len())
+}
+
+/// plays a recording back through the same key handling as run_app
+fn replay_app<B: Backend>(
+    terminal: &mut Terminal<B>,
+    args: Args,
+    replay: Replay,
+) -> io::Result<()> {
+    let mut app = replay_start(args.clone(), &replay);
+    // recorded gaps below this are kept as they are, so double presses still register
+    let longest_pause = args
+        .replay_delay
+        .max(DOUBLE_PRESS_WINDOW + Duration::from_millis(1));
+    for (code, gap) in replay.keys {
+        app.expire_notifications();
+        terminal.draw(|f| ui(f, &mut app))?;
+        // q or Esc from the real keyboard stops the replay early
+        if event::poll(gap.min(longest_pause))? {
+            if let Event::Key(key) = event::read()? {
+                if matches!(key.code, KeyCode::Char('q') | KeyCode::Esc) {
+                    return Ok(());
+                }
+            }
+        }
+        if handle_key(&mut app, code) {
+            return Ok(());
+        }
+        while app.settling_drop.is_some() {
+            terminal.draw(|f| ui(f, &mut app))?;
+            std::thread::sleep(GRAVITY_FRAME);
+            app.settle_step();
+        }
+    }
+    app.set_status_message(String::from("replay finished, any key to quit"));
+    loop {
+        terminal.draw(|f| ui(f, &mut app))?;
+        if let Event::Key(_) = event::read()? {
+            return Ok(());
+        }
+    }
+}
+
+/// applies one key press through whatever screen is showing, true when it quits
+fn handle_key(app: &mut App, code: KeyCode) -> bool {
+    if app.confirming_quit {
+        if code == KeyCode::Char('y') {
+            return true;
+        }
+        app.confirming_quit = false;
+        return false;
+    }
+    // only resume and quit get through a pause
+    if app.paused {
+        if code == KeyCode::Char('p') {
+            app.paused = false;
+        } else if app.args.keybindings.is(code, Action::Quit) {
+            if !app.has_unsaved_progress() {
+                return true;
+            }
+            app.confirming_quit = true;
+        }
+        return false;
+    }
+    if app.command_input.is_some() {
+        app.handle_command_key(code);
+        return false;
+    }
+    if app.showing_help {
+        app.showing_help = false;
+        return false;
+    }
+    if code == KeyCode::Char('?') {
+        app.showing_help = true;
+        return false;
+    }
+    let keybindings = app.args.keybindings.clone();
+    // quit backs out of targeting rather than out of the game
+    if app.targeting_slot.is_some()
+        && (keybindings.is(code, Action::Quit) || keybindings.is(code, Action::Cancel))
+    {
+        app.cancel_targeting();
+        return false;
+    }
+    if app.stepped_drop.is_some() && !keybindings.is(code, Action::Quit) {
+        app.step_combat();
+        return false;
+    }
+    if keybindings.is(code, Action::Quit) {
+        if app.has_unsaved_progress() {
+            app.confirming_quit = true;
+            return false;
+        }
+        return true;
+    }
+    if let Some(m) = app.menu {
+        let num_items = menu_len(m, app);
+        match code {
+            code if keybindings.is(code, Action::Cancel) => app.menu = None,
+            code if code == m.toggle_key() => app.menu = None,
+            code if keybindings.is(code, Action::MoveDown) => {
+                move_list_selection(&mut app.menu_state, CursorMove::Down, num_items)
+            }
+            code if keybindings.is(code, Action::MoveUp) => {
+                move_list_selection(&mut app.menu_state, CursorMove::Up, num_items)
+            }
+            _ => {}
+        }
+        return false;
+    }
+    match app.game_state() {
+        GameState::ChoosingImprovement(num_choices) => {
+            // choosing improvement
+            match keybindings.improvement_action(code) {
+                Some(Action::ToggleChoice) => {
+                    let index = app.improvement_list_state.selected().unwrap_or(0);
+                    app.toggle_improvement_choice(index);
+                }
+                Some(Action::ConfirmChoices) => app.confirm_improvements(),
+                Some(Action::Cancel) => app.cancel_improvement_choices(),
+                Some(Action::MoveDown) => move_list_selection(
+                    &mut app.improvement_list_state,
+                    CursorMove::Down,
+                    num_choices,
+                ),
+                Some(Action::MoveUp) => move_list_selection(
+                    &mut app.improvement_list_state,
+                    CursorMove::Up,
+                    num_choices,
+                ),
+                _ => match code {
+                    KeyCode::Char('p') => app.paused = true,
+                    KeyCode::Char(c @ '1'..='9') => {
+                        let index = c as usize - '1' as usize;
+                        if index < num_choices {
+                            app.improvement_list_state.select(Some(index));
+                            app.toggle_improvement_choice(index);
+                        }
+                    }
+                    _ => {}
+                },
+            }
+        }
+        GameState::Paused => {}
+        GameState::Victory | GameState::GameOver => {
+            if code == KeyCode::Char('r') {
+                app.restart();
+            }
+        }
+        GameState::Abilities => app.handle_abilities_key(code),
+        GameState::ChoosingLoadout => app.handle_loadout_key(code),
+        GameState::TargetingAbility(_) => match keybindings.targeting_action(code) {
+            Some(Action::MoveUp) => app.move_playing_cursor(CursorMove::Up),
+            Some(Action::MoveDown) => app.move_playing_cursor(CursorMove::Down),
+            Some(Action::MoveLeft) => app.move_playing_cursor(CursorMove::Left),
+            Some(Action::MoveRight) => app.move_playing_cursor(CursorMove::Right),
+            Some(Action::Select) | Some(Action::ConfirmChoices) => app.target_ability(
+                tile_position_from_cursor_position(app.playing_cursor_position, app.board()),
+            ),
+            _ => {}
+        },
+        GameState::Playing => {
+            // playing on board
+            // any key other than a repeated jump ends the jump cycle
+            let jump_cycle = app.jump_cycle.take();
+            // likewise for a pending big swing confirmation
+            let confirming_big_swing = app.confirming_big_swing;
+            app.confirming_big_swing = false;
+            if app.awaiting_jump_glyph {
+                app.awaiting_jump_glyph = false;
+                if let KeyCode::Char(c) = code {
+                    app.jump_to_nearest(JumpTarget::Glyph(c), jump_cycle);
+                }
+                return false;
+            }
+            match keybindings.board_action(code) {
+                Some(Action::Drop) => app.request_drop(confirming_big_swing),
+                Some(Action::Select) => app.select_under_cursor(),
+                Some(Action::MoveUp) => app.move_playing_cursor(CursorMove::Up),
+                Some(Action::MoveDown) => app.move_playing_cursor(CursorMove::Down),
+                Some(Action::MoveLeft) => app.move_playing_cursor(CursorMove::Left),
+                Some(Action::MoveRight) => app.move_playing_cursor(CursorMove::Right),
+                Some(Action::CastAbility1) => app.cast_ability(0),
+                Some(Action::CastAbility2) => app.cast_ability(1),
+                Some(Action::CastAbility3) => app.cast_ability(2),
+                Some(Action::CastAbility4) => app.cast_ability(3),
+                // keys that aren't remappable
+                _ => match code {
+                    KeyCode::Char('a') => app.open_menu(Menu::Achievements),
+                    KeyCode::Char('s') => app.save_game(),
+                    KeyCode::Char('p') => app.paused = true,
+                    KeyCode::Char('L') => app.open_menu(Menu::LifetimeStats),
+                    KeyCode::Char('P') => app.open_menu(Menu::Specials),
+                    KeyCode::Char('i') => app.open_menu(Menu::RunStats),
+                    KeyCode::Char('A') => app.ability_list_state.select(Some(0)),
+                    KeyCode::Char('H') => {
+                        app.settings.layout.hud_position = app.settings.layout.hud_position.next();
+                        app.save_settings();
+                    }
+                    KeyCode::Char('z') => app.hud_hidden = !app.hud_hidden,
+                    KeyCode::Char('F') => {
+                        app.settings.animate_gravity = !app.settings.animate_gravity;
+                        app.save_settings();
+                        app.set_status_message(format!(
+                            "falling tiles {}",
+                            if app.settings.animate_gravity {
+                                "animated"
+                            } else {
+                                "not animated"
+                            }
+                        ));
+                    }
+                    KeyCode::Char('G') => {
+                        app.settings.layout.show_guide_labels =
+                            !app.settings.layout.show_guide_labels;
+                        app.save_settings();
+                    }
+                    KeyCode::Char('R') => {
+                        app.settings.layout.show_run_info = !app.settings.layout.show_run_info;
+                        app.save_settings();
+                    }
+                    KeyCode::Char('C') => {
+                        app.settings.coaching = !app.settings.coaching;
+                        app.save_settings();
+                        app.set_status_message(format!(
+                            "coaching {}",
+                            if app.settings.coaching { "on" } else { "off" }
+                        ));
+                    }
+                    KeyCode::Char('e') => app.jump_to_nearest(JumpTarget::Enemy, jump_cycle),
+                    KeyCode::Char('.') => app.repeat_last_selection(),
+                    KeyCode::Char('u') => app.undo_last_turn(),
+                    KeyCode::Char('g') => {
+                        app.awaiting_jump_glyph = true;
+                        app.jump_cycle = jump_cycle;
+                        let glyphs: Vec<String> = [
+                            TileType::Potion,
+                            TileType::Shield,
+                            TileType::Coin,
+                            TileType::Sword,
+                            TileType::Enemy,
+                            TileType::Special,
+                        ]
+                        .into_iter()
+                        .map(|tt| blot_char_from_tile_type(tt, &app.theme).to_string())
+                        .collect();
+                        app.set_status_message(format!("jump to: {}", glyphs.join(" ")));
+                    }
+                    KeyCode::Char('n') => app.cycle_hint(),
+                    // take back the last tile added to the chain
+                    KeyCode::Backspace => {
+                        app.hints.clear();
+                        app.last_select_press = None;
+                        app.game.unselect_last_tile();
+                        if let Some((end, _)) = selected_tiles(&app.game).last() {
+                            app.playing_cursor_position =
+                                cursor_position_from_tile_position(end, app.board());
+                        }
+                    }
+                    KeyCode::Char(':') => app.command_input = Some(String::new()),
+                    _ => {}
+                },
+            }
+        }
+    }
+    false
+}
+
+fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
+    app.frame_size = f.size();
+    // the board is drawn at a fixed size, so there's nothing sensible to show below it;
+    // the hud gets whatever is left and is cut off where it doesn't fit
+    let board = app.board();
+    if f.size().width <= board.max_right() || f.size().height <= board.max_down() {
+        f.render_widget(
+            Paragraph::new("terminal too small, please enlarge it").wrap(Wrap { trim: true }),
+            f.size(),
+        );
+        return;
+    }
+    let draining_shields = app
+        .shield_drain
+        .as_ref()
+        .and_then(|drain| drain.displayed_shields(app.game.player().being.shields as u64));
+    if draining_shields.is_none() {
+        app.shield_drain = None;
+    }
+    let coaching_tip = app.coaching_tip();
+    if let GameState::ChoosingImprovement(num_choices) = app.game_state() {
+        app.improvement_scroll_offset = scroll_offset_for(
+            app.improvement_list_state.selected().unwrap_or(0),
+            app.improvement_scroll_offset,
+            board.max_down() as usize,
+            num_choices,
+        );
+    }
+    let game_widget = GameWidget {
+        game: &app.game,
+        cursor_pos: app.playing_cursor_position,
+        improvement_choice_indeces: &app.improvement_choice_indeces,
+        improvement_list_state: &mut app.improvement_list_state,
+        status_message: app
+            .status_message
+            .as_ref()
+            .map(|msg| msg.text.as_str())
+            .or(coaching_tip),
+        draining_shields,
+        objective_display: app.mode.objective_display(&app.progress, &app.game),
+        background: app.background,
+        theme: &app.theme,
+        number_format: app.settings.number_format,
+        stat_display: app.settings.stat_display,
+        hud_area: hud_area(app.settings.layout.hud_position, board, f.size()),
+        selection_start_style: app.settings.effective_selection_start_style(),
+        new_ability_slots: &app.new_ability_slots,
+        path_style: app.settings.path_style,
+        show_guide_labels: app.settings.layout.show_guide_labels,
+        show_hud: !app.hud_hidden,
+        wide_glyphs: app.wide_glyphs,
+        improvement_confirm_hint: match app.settings.improvement_commit {
+            ImprovementCommit::Instant => None,
+            ImprovementCommit::SpaceThenEnter => Some("Enter to confirm"),
+            ImprovementCommit::EnterWithPreview if app.confirming_improvements => {
+                Some("Enter again to confirm")
+            }
+            ImprovementCommit::EnterWithPreview => Some("Enter to review"),
+        },
+        incoming_damage_style: incoming_damage_style(
+            &app.game,
+            app.settings.reduced_motion,
+            app.started_at.elapsed(),
+        ),
+        hint_path: app
+            .hints
+            .get(app.hint_index)
+            .map(|hint| hint.path.as_slice()),
+    };
+
+    // the board sits at the frame's top left, which the cursor to tile math relies on,
+    // with the hud beside or below it
+    f.render_widget(game_widget, f.size());
+
+    if app.settings.layout.show_run_info && !app.hud_hidden {
+        // kept in the top right corner, clear of the board and HUD
+        let mut run_info = format!(
+            "{} | {} | turn {} | seed {}",
+            app.mode.name(),
+            difficulty_name(app.game.difficulty()),
+            app.game.turn(),
+            app.game.seed()
+        );
+        if app.gravity != GravityDirection::Down {
+            run_info += format!(" | gravity {}", gravity_name(app.gravity)).as_str();
+        }
+        if matches!(app.game.selection_mode(), SelectionMode::FourWay) {
+            run_info += " | no diagonals";
+        }
+        let width = (run_info.len() as u16).min(f.size().width);
+        f.render_widget(
+            Paragraph::new(run_info).style(Style::default().fg(Color::DarkGray)),
+            Rect::new(f.size().width - width, 0, width, 1),
+        );
+    }
+
+    if let Some(ref flash) = app.flash {
+        if flash.started_at.elapsed() < FLASH_DURATION {
+            f.render_widget(Tint(flash.color), f.size());
+        } else {
+            app.flash = None;
+        }
+    }
+
+    // toasts stack upwards from the bottom right corner, newest at the bottom
+    let toasts = if app.hud_hidden {
+        &[][..]
+    } else {
+        &app.toasts[..]
+    };
+    for (idx, toast) in toasts.iter().rev().enumerate() {
+        let width = (toast.text.len() as u16 + 2).min(f.size().width);
+        let y = f.size().height.saturating_sub(3 + 3 * idx as u16);
+        if y == 0 {
+            break;
+        }
+        let area = Rect::new(f.size().width - width, y, width, 3);
+        f.render_widget(Clear, area);
+        f.render_widget(
+            Paragraph::new(toast.text.as_str()).block(Block::default().borders(Borders::ALL)),
+            area,
+        );
+    }
+
+    let victory = matches!(app.game_state(), GameState::Victory);
+    let game_over = matches!(app.game_state(), GameState::GameOver);
+    let managing_abilities = matches!(app.game_state(), GameState::Abilities);
+    if app.menu.is_some()
+        || app.confirming_quit
+        || app.showing_help
+        || app.paused
+        || victory
+        || game_over
+        || managing_abilities
+    {
+        f.render_widget(Dim, f.size());
+    }
+
+    if app.paused {
+        let area = centered_rect(30, 4, f.size());
+        f.render_widget(Clear, area);
+        f.render_widget(
+            Paragraph::new(vec![
+                Spans::from(Span::styled(
+                    "PAUSED",
+                    Style::default().add_modifier(Modifier::BOLD),
+                )),
+                Spans::from(format!(
+                    "p to resume, {} to quit",
+                    app.args.keybindings.describe(Action::Quit)
+                )),
+            ])
+            .alignment(Alignment::Center)
+            .block(Block::default().borders(Borders::ALL)),
+            area,
+        );
+    }
+
+    if game_over {
+        let stats = app.game.stats();
+        let area = centered_rect(50, 10, f.size());
+        let mut game_over_text = vec![Spans::from(Span::styled(
+            "Game Over",
+            Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+        ))];
+        let mut border_style = Style::default();
+        if let Some(banner) = app.new_best {
+            let mut banner_style = Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD);
+            if !app.settings.reduced_motion {
+                banner_style = banner_style.add_modifier(Modifier::SLOW_BLINK);
+            }
+            game_over_text.push(Spans::from(Span::styled(banner, banner_style)));
+            border_style = border_style.fg(Color::Yellow);
+        }
+        game_over_text.extend([
+            Spans::from(format!("final score {}", app.game.score())),
+            Spans::from(format!(
+                "survived {} turns, reached level {}",
+                app.game.turn(),
+                app.level
+            )),
+            // the core's totals, the same ones the run stats menu shows; the chain length is
+            // exact on this side since it's just the size of each dropped selection
+            Spans::from(format!(
+                "{} enemies slain, {} coins, longest chain {}",
+                stats.enemies_slain, stats.coins_collected, app.progress.longest_chain
+            )),
+            Spans::from(format!(
+                "{} damage dealt, {} potions, {} shields, {} casts",
+                stats.damage_dealt, stats.potions_drunk, stats.shields_gained, stats.abilities_cast
+            )),
+        ]);
+        // only once the seed has been played before, a first run would just repeat its score
+        if let Some(previous) = app.previous_seed_best {
+            game_over_text.push(Spans::from(format!(
+                "best on this seed: {}",
+                previous.max(app.game.score())
+            )));
+        }
+        game_over_text.push(Spans::from(format!(
+            "press r to restart or {} to quit",
+            app.args.keybindings.describe(Action::Quit)
+        )));
+        f.render_widget(Clear, area);
+        f.render_widget(
+            Paragraph::new(game_over_text)
+                .alignment(Alignment::Center)
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .border_style(border_style),
+                ),
+            area,
+        );
+    }
+
+    if let GameState::ChoosingLoadout = app.game_state() {
+        let ability_types = app.game.available_ability_types();
+        let items: Vec<ListItem> = ability_types
+            .iter()
+            .enumerate()
+            .map(|(idx, (ability_type, (name, description)))| {
+                let item = ListItem::new(format!(
+                    "{} - {}{}",
+                    name,
+                    description,
+                    passive_tag(*ability_type)
+                ));
+                if app.loadout_choice_indeces.contains(&idx) {
+                    item.style(Style::default().bg(Color::White).fg(Color::Black))
+                } else {
+                    item
+                }
+            })
+            .collect();
+        let title = format!(
+            "Starting abilities, {} of {} ({} pick, r random, {} start)",
+            app.loadout_choice_indeces.len(),
+            app.game.player().abilities.len(),
+            app.args.keybindings.describe(Action::ToggleChoice),
+            app.args.keybindings.describe(Action::ConfirmChoices)
+        );
+        let list = List::new(items)
+            .block(Block::default().title(title).borders(Borders::ALL))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+            .highlight_symbol(">");
+        f.render_widget(Clear, f.size());
+        f.render_stateful_widget(list, f.size(), &mut app.loadout_list_state);
+    }
+
+    if managing_abilities {
+        let items: Vec<ListItem> = app
+            .game
+            .player()
+            .abilities
+            .iter()
+            .enumerate()
+            .map(|(idx, ability_opt)| match ability_opt {
+                Some(a) => {
+                    let (name, description) = a.ability_type.name_description();
+                    ListItem::new(format!(
+                        "{}: {} - {}{}",
+                        idx + 1,
+                        name,
+                        description,
+                        passive_tag(a.ability_type)
+                    ))
+                }
+                None => ListItem::new(format!("{}: [empty]", idx + 1)),
+            })
+            .collect();
+        let area = menu_area(items.len(), f.size());
+        let list = List::new(items)
+            .block(
+                Block::default()
+                    .title(format!(
+                        "Abilities (J/K move, d drop, {} close)",
+                        app.args.keybindings.describe(Action::Cancel)
+                    ))
+                    .borders(Borders::ALL),
+            )
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        f.render_widget(Clear, area);
+        f.render_stateful_widget(list, area, &mut app.ability_list_state);
+    }
+
+    if victory {
+        let area = centered_rect(40, 5, f.size());
+        let victory_text = vec![
+            Spans::from(Span::styled(
+                "Victory!",
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD),
+            )),
+            Spans::from(format!(
+                "{} specials slain in {} turns",
+                app.progress.specials_slain,
+                app.game.turn()
+            )),
+            Spans::from(format!(
+                "press r to restart or {} to quit",
+                app.args.keybindings.describe(Action::Quit)
+            )),
+        ];
+        f.render_widget(Clear, area);
+        f.render_widget(
+            Paragraph::new(victory_text)
+                .alignment(Alignment::Center)
+                .block(Block::default().borders(Borders::ALL)),
+            area,
+        );
+    }
+
+    match app.menu {
+        Some(m) => {
+            let (title, items): (&str, Vec<ListItem>) = match m {
+                Menu::Achievements => (
+                    "Achievements",
+                    ACHIEVEMENTS
+                        .iter()
+                        .map(|a| {
+                            if app.achievements.is_unlocked(a) {
+                                ListItem::new(format!("[x] {} - {}", a.name, a.description))
+                                    .style(Style::default().fg(Color::Green))
+                            } else {
+                                ListItem::new(format!("[ ] {} - {}", a.name, a.description))
+                            }
+                        })
+                        .collect(),
+                ),
+                Menu::LifetimeStats => (
+                    "Lifetime Stats",
+                    app.lifetime_stats
+                        .display_lines()
+                        .into_iter()
+                        .map(ListItem::new)
+                        .collect(),
+                ),
+                Menu::RunStats => (
+                    "This Run",
+                    run_stats_lines(&app.game)
+                        .into_iter()
+                        .map(ListItem::new)
+                        .collect(),
+                ),
+                Menu::Specials => {
+                    let lines = special_lines(&app.game);
+                    if lines.is_empty() {
+                        ("Specials", vec![ListItem::new("no specials on the board")])
+                    } else {
+                        ("Specials", lines.into_iter().map(ListItem::new).collect())
+                    }
+                }
+            };
+            let area = menu_area(items.len(), f.size());
+            let list = List::new(items)
+                .block(Block::default().title(title).borders(Borders::ALL))
+                .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+            f.render_widget(Clear, area);
+            f.render_stateful_widget(list, area, &mut app.menu_state);
+            // a small copy of the board in the corner while the menu covers it
+            let preview_width = board.width * board.blot_width() + 2;
+            let preview_height = board.height + 2;
+            if f.size().width >= preview_width && f.size().height > preview_height {
+                let preview_area = Rect::new(
+                    f.size().width - preview_width,
+                    1,
+                    preview_width,
+                    preview_height,
+                );
+                f.render_widget(Clear, preview_area);
+                f.render_widget(
+                    BoardPreview {
+                        game: &app.game,
+                        background: app.background,
+                        theme: &app.theme,
+                        wide_glyphs: app.wide_glyphs,
+                    },
+                    preview_area,
+                );
+            }
+        }
+        None => {
+            if let GameState::Playing | GameState::TargetingAbility(_) = app.game_state() {
+                f.set_cursor(app.playing_cursor_position.0, app.playing_cursor_position.1);
+            }
+        }
+    }
+
+    let bottom_y = f.size().height.saturating_sub(1);
+    if app.debug {
+        let log_height = app.debug_log.len() as u16;
+        let log_lines: Vec<Spans> = app
+            .debug_log
+            .iter()
+            .map(|line| Spans::from(line.as_str()))
+            .collect();
+        f.render_widget(
+            Paragraph::new(log_lines).style(Style::default().fg(Color::DarkGray)),
+            Rect::new(
+                0,
+                bottom_y.saturating_sub(log_height),
+                f.size().width,
+                log_height.min(bottom_y),
+            ),
+        );
+    }
+    if let Some(ref input) = app.command_input {
+        let prompt = format!(":{}", input);
+        let prompt_len = prompt.len() as u16;
+        f.render_widget(Clear, Rect::new(0, bottom_y, f.size().width, 1));
+        f.render_widget(
+            Paragraph::new(prompt),
+            Rect::new(0, bottom_y, f.size().width, 1),
+        );
+        f.set_cursor(prompt_len.min(f.size().width.saturating_sub(1)), bottom_y);
+    }
+
+    if app.showing_help {
+        let lines = help_lines(&app.args.keybindings);
+        let key_width = lines.iter().map(|(k, _)| k.len()).max().unwrap_or(0);
+        let help_text: Vec<Spans> = lines
+            .iter()
+            .map(|(keys, action)| {
+                Spans::from(vec![
+                    Span::styled(
+                        format!("{:width$}  ", keys, width = key_width),
+                        Style::default().add_modifier(Modifier::BOLD),
+                    ),
+                    Span::raw(*action),
+                ])
+            })
+            .collect();
+        let area = centered_rect(80, lines.len() as u16 + 2, f.size());
+        f.render_widget(Clear, area);
+        f.render_widget(
+            Paragraph::new(help_text).block(
+                Block::default()
+                    .title("Keys (any key to close)")
+                    .borders(Borders::ALL),
+            ),
+            area,
+        );
+    }
+
+    if app.confirming_quit {
+        let area = centered_rect(46, 3, f.size());
+        let prompt = Paragraph::new("Unsaved progress will be lost. Quit? (y/n)")
+            .block(Block::default().borders(Borders::ALL))
+            .wrap(Wrap { trim: true });
+        f.render_widget(Clear, area);
+        f.render_widget(prompt, area);
+    }
+}
+
+/// (keys, action) pairs for the '?' overlay, grouped by screen; remapped keys are shown as bound
+fn help_lines(keybindings: &Keybindings) -> Vec<(String, &'static str)> {
+    let bound = |actions: &[Action]| -> String {
+        let described: Vec<String> = actions.iter().map(|a| keybindings.describe(*a)).collect();
+        described.join(", ")
+    };
+    vec![
+        (
+            bound(&[
+                Action::MoveLeft,
+                Action::MoveDown,
+                Action::MoveUp,
+                Action::MoveRight,
+            ]),
+            "move the cursor",
+        ),
+        (
+            bound(&[Action::Select]),
+            "select the tile under the cursor, twice to grab the chain",
+        ),
+        (
+            String::new(),
+            "a sword chain can run through enemies, up to one that survives",
+        ),
+        (String::from("Backspace"), "undo the last selected tile"),
+        (bound(&[Action::Drop]), "drop the selection"),
+        (
+            bound(&[
+                Action::CastAbility1,
+                Action::CastAbility2,
+                Action::CastAbility3,
+                Action::CastAbility4,
+            ]),
+            "cast an ability",
+        ),
+        (String::from("n"), "cycle suggested moves"),
+        (
+            String::from("e / g + glyph"),
+            "jump to an enemy / a tile type",
+        ),
+        (String::from("."), "repeat the last selection shape"),
+        (String::from("u"), "undo the last turn, once"),
+        (String::from("A"), "manage abilities"),
+        (
+            String::from("a L P i"),
+            "achievements, lifetime stats, specials, this run",
+        ),
+        (String::from("s"), "save the run"),
+        (String::from("p"), "pause"),
+        (String::from(":"), "command prompt"),
+        (
+            String::from("C F G H R z"),
+            "coaching, falling tiles, guides, HUD position, run info, hide HUD",
+        ),
+        (String::new(), ""),
+        (
+            bound(&[Action::MoveUp, Action::MoveDown]),
+            "improvements: move",
+        ),
+        (
+            format!("{}, 1-9", bound(&[Action::ToggleChoice])),
+            "improvements: toggle a choice",
+        ),
+        (bound(&[Action::ConfirmChoices]), "improvements: confirm"),
+        (
+            bound(&[Action::Cancel]),
+            "improvements: clear choices; close a list, cancel a cast",
+        ),
+        (String::from("r"), "restart after game over or victory"),
+        (String::from("?"), "this help"),
+        (bound(&[Action::Quit]), "quit"),
+    ]
+}
+
+const SIDE_HUD_MIN_WIDTH: u16 = 32;
+const BOTTOM_HUD_HEIGHT: u16 = 24;
+
+/// the hud sits below the board, or in a column beside it when there isn't room below
+fn hud_area(position: HudPosition, board: BoardSize, area: Rect) -> Rect {
+    let board_width = board.max_right() + 1;
+    let board_height = board.max_down() + 1;
+    let side = match position {
+        HudPosition::Bottom => false,
+        HudPosition::Side => true,
+        HudPosition::Auto => {
+            area.height < board_height + BOTTOM_HUD_HEIGHT
+                && area.width >= board_width + 2 + SIDE_HUD_MIN_WIDTH
+        }
+    };
+    if side {
+        Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Length(board_width + 2), Constraint::Min(0)])
+            .split(area)[1]
+    } else {
+        Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(board_height), Constraint::Min(0)])
+            .split(area)[1]
+    }
+}
+
+fn menu_len(menu: Menu, app: &App) -> usize {
+    match menu {
+        Menu::Achievements => ACHIEVEMENTS.len(),
+        Menu::LifetimeStats => app.lifetime_stats.display_lines().len(),
+        Menu::Specials => special_lines(&app.game).len().max(1),
+        Menu::RunStats => run_stats_lines(&app.game).len(),
+    }
+}
+
+/// the core's totals for the current run
+fn run_stats_lines(game: &Game) -> Vec<String> {
+    let stats = game.stats();
+    vec![
+        format!("coins collected: {}", stats.coins_collected),
+        format!("damage dealt: {}", stats.damage_dealt),
+        format!("enemies slain: {}", stats.enemies_slain),
+        format!("potions drunk: {}", stats.potions_drunk),
+        format!("shields gained: {}", stats.shields_gained),
+        format!("abilities cast: {}", stats.abilities_cast),
+    ]
+}
+
+/// when a special's end of turn behavior next fires
+fn special_readiness(running_cooldown: u32) -> String {
+    if running_cooldown > 0 {
+        format!("acts in {}", running_cooldown)
+    } else {
+        String::from("acts this turn")
+    }
+}
+
+/// one line per special on the board: position, type, stats and cooldown
+fn special_lines(game: &Game) -> Vec<String> {
+    game.specials()
+        .into_iter()
+        .filter_map(|(tp, t, _sid)| match t.tile_info {
+            TileInfo::Special(special) => Some(format!(
+                "({}, {}) {}: hp {}/{}, sh {}/{}, dmg {}, {}",
+                tp.y,
+                tp.x,
+                special.special_type.name_description().0,
+                special.being.hit_points,
+                special.being.max_hit_points,
+                special.being.shields,
+                special.being.max_shields,
+                special.being.base_output_damage,
+                special_readiness(special.running_cooldown as u32)
+            )),
+            _ => None,
+        })
+        .collect()
+}
+
+fn menu_area(num_items: usize, area: Rect) -> Rect {
+    // items plus the border above and below
+    centered_rect(60, num_items as u16 + 2, area)
+}
+
+fn centered_rect(width: u16, height: u16, area: Rect) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    )
+}
+
+// the turn helper the integration tests use too
+#[cfg(test)]
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::play_turn;
+    use dungeon_raid_core::game::special::SpecialId;
+
+    static EMPTY_CHOICES: Vec<usize> = Vec::new();
+
+    fn game_widget<'a>(
+        game: &'a Game,
+        theme: &'a Theme,
+        improvement_list_state: &'a mut ListState,
+        hud_area: Rect,
+    ) -> GameWidget<'a> {
+        GameWidget {
+            game,
+            cursor_pos: (0, 0),
+            improvement_choice_indeces: &EMPTY_CHOICES,
+            improvement_list_state,
+            status_message: Some("status"),
+            draining_shields: None,
+            objective_display: None,
+            background: Background::Dark,
+            theme,
+            number_format: NumberFormat::Raw,
+            stat_display: StatDisplay::Fraction,
+            hud_area,
+            selection_start_style: SelectionStartStyle::Blink,
+            new_ability_slots: &[],
+            incoming_damage_style: Style::default(),
+            path_style: PathStyle::Ascii,
+            show_guide_labels: true,
+            show_hud: true,
+            wide_glyphs: false,
+            improvement_confirm_hint: None,
+            hint_path: None,
+        }
+    }
+
+    // an app on a fixed seed that's past choosing its loadout, without touching save.toml
+    fn test_app() -> App {
+        let args = Args {
+            mode: GameMode::Endless,
+            background: Background::Dark,
+            board: None,
+            gravity: GravityDirection::Down,
+            difficulty: Difficulty::Normal,
+            seed: Some(1234),
+            record: None,
+            replay: None,
+            verify_replay: None,
+            replay_delay: Duration::ZERO,
+            no_mouse: true,
+            debug: false,
+            keybindings: Keybindings::default(),
+        };
+        let mut app = App::with_settings(&args, Settings::default());
+        app.choosing_loadout = false;
+        app
+    }
+
+    #[test]
+    fn right_click_cancels_targeting_without_spending_anything() {
+        let mut app = test_app();
+        // whichever ability asks for a target first
+        let needs_target = app
+            .game
+            .available_ability_types()
+            .into_iter()
+            .map(|(ability_type, _)| ability_type)
+            .find(|ability_type| {
+                app.game.set_ability(0, *ability_type);
+                app.cast_ability(0);
+                app.targeting_slot.is_some()
+            });
+        assert!(needs_target.is_some(), "no ability asks for a target");
+        let cooldown = |app: &App| {
+            app.game.player().abilities[0]
+                .as_ref()
+                .map(|a| a.running_cooldown)
+        };
+        let (cooldown_before, game_before) = (cooldown(&app), app.game.to_json().unwrap());
+        app.handle_targeting_mouse(MouseEvent {
+            kind: MouseEventKind::Down(MouseButton::Right),
+            column: 0,
+            row: 0,
+            modifiers: crossterm::event::KeyModifiers::NONE,
+        });
+        assert!(app.targeting_slot.is_none());
+        assert_eq!(cooldown(&app), cooldown_before);
+        assert_eq!(app.game.to_json().unwrap(), game_before);
+    }
+
+    #[test]
+    fn game_widget_renders_into_a_zero_size_area() {
+        let (game, theme, mut list_state) =
+            (Game::default(), Theme::default(), ListState::default());
+        let mut buf = Buffer::empty(Rect::default());
+        let widget = game_widget(&game, &theme, &mut list_state, Rect::new(0, 30, 80, 30));
+        widget.render(Rect::default(), &mut buf);
+    }
+
+    #[test]
+    fn game_widget_draws_relative_to_its_area_and_stays_inside_it() {
+        let (game, theme, mut list_state) =
+            (Game::default(), Theme::default(), ListState::default());
+        let mut buf = Buffer::empty(Rect::new(0, 0, 100, 40));
+        let area = Rect::new(10, 5, 20, 8);
+        // a hud hanging past the area's bottom right must be cut off rather than panic
+        let widget = game_widget(&game, &theme, &mut list_state, Rect::new(0, 6, 60, 30));
+        widget.render(area, &mut buf);
+        // the first tile lands on the area's top left
+        assert_ne!(buf.get(area.x, area.y).symbol, " ");
+        for y in 0..buf.area.height {
+            for x in 0..buf.area.width {
+                let inside =
+                    x >= area.left() && x < area.right() && y >= area.top() && y < area.bottom();
+                if !inside {
+                    assert_eq!(
+                        buf.get(x, y).symbol,
+                        " ",
+                        "drew outside the area at {},{}",
+                        x,
+                        y
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn hud_is_cut_off_rather_than_panicking_in_a_short_terminal() {
+        let (game, theme) = (Game::default(), Theme::default());
+        let area = Rect::new(0, 0, 80, 14);
+        for position in [HudPosition::Side, HudPosition::Bottom, HudPosition::Auto] {
+            let mut list_state = ListState::default();
+            let hud = hud_area(position, BoardSize::of(&game, false), area);
+            let mut buf = Buffer::empty(area);
+            game_widget(&game, &theme, &mut list_state, hud).render(area, &mut buf);
+        }
+    }
+
+    #[test]
+    fn renders_without_any_abilities() {
+        let (mut game, theme, mut list_state) =
+            (Game::default(), Theme::default(), ListState::default());
+        for slot in 0..game.player().abilities.len() {
+            game.drop_ability(slot);
+        }
+        let area = Rect::new(0, 0, 100, 60);
+        let hud = hud_area(HudPosition::Bottom, BoardSize::of(&game, false), area);
+        let mut buf = Buffer::empty(area);
+        game_widget(&game, &theme, &mut list_state, hud).render(area, &mut buf);
+        let text: String = buf
+            .content
+            .iter()
+            .map(|cell| cell.symbol.as_str())
+            .collect();
+        assert!(text.contains("no abilities yet"));
+    }
+
+    #[test]
+    fn improvement_toggles_stop_at_the_number_to_choose() {
+        let mut app = test_app();
+        for _ in 0..300 {
+            if app.game.improvement_choice_set().is_some() || app.game.is_game_over() {
+                break;
+            }
+            play_turn(&mut app.game);
+        }
+        let num_to_choose = app
+            .game
+            .improvement_choice_set()
+            .expect("never offered an improvement")
+            .num_to_choose;
+        let num_choices = match app.game_state() {
+            GameState::ChoosingImprovement(num_choices) => num_choices,
+            _ => unreachable!("an improvement set is chosen before anything else"),
+        };
+        for index in 0..num_choices {
+            app.toggle_improvement_choice(index);
+        }
+        assert_eq!(app.improvement_choice_indeces.len(), num_to_choose);
+    }
+
+    // the game as json after a turn of tests/common's play_turn
+    fn play_turn_json(game: &mut Game) -> String {
+        play_turn(game);
+        game.to_json().unwrap()
+    }
+
+    // each special's cooldown, by its id
+    fn special_cooldowns(game: &Game) -> Vec<(SpecialId, u32)> {
+        game.specials()
+            .into_iter()
+            .filter_map(|(_tp, t, sid)| match t.tile_info {
+                TileInfo::Special(special) => Some((sid, special.running_cooldown as u32)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn several_specials_count_down_their_own_cooldowns() {
+        let mut game = Game::with_seed(1234);
+        let mut most_at_once = 0;
+        for _ in 0..300 {
+            if game.is_game_over() {
+                break;
+            }
+            let before = special_cooldowns(&game);
+            play_turn(&mut game);
+            let after = special_cooldowns(&game);
+            most_at_once = most_at_once.max(after.len());
+            // one that acted starts its cooldown over, the rest tick down by exactly one
+            for (sid, cooldown) in before.iter().filter(|(_sid, cooldown)| *cooldown > 0) {
+                if let Some((_sid, next)) = after.iter().find(|(other, _)| other == sid) {
+                    assert_eq!(*next, cooldown - 1);
+                }
+            }
+        }
+        assert!(most_at_once > 1, "never saw more than one special at once");
+    }
+
+    #[test]
+    fn restored_snapshot_plays_out_the_same() {
+        let mut game = Game::with_seed(1234);
+        play_turn(&mut game);
+        let snapshot = game.snapshot();
+        let first: Vec<String> = (0..10).map(|_| play_turn_json(&mut game)).collect();
+        game.restore(&snapshot);
+        let second: Vec<String> = (0..10).map(|_| play_turn_json(&mut game)).collect();
+        assert_eq!(first, second);
+    }
+
+    // walks `steps` moves from the top of a `num_items` list shown `height` rows at a time,
+    // returning where the selection and the scroll offset end up
+    fn navigate(num_items: usize, height: usize, steps: &[CursorMove]) -> (usize, usize) {
+        let (mut selected, mut offset) = (0, 0);
+        for m in steps {
+            selected = list_step(selected, *m, num_items);
+            offset = scroll_offset_for(selected, offset, height, num_items);
+        }
+        (selected, offset)
+    }
+
+    #[test]
+    fn scrolls_to_keep_the_selection_visible_past_the_viewport() {
+        let down = [CursorMove::Down; 7];
+        assert_eq!(navigate(10, 5, &down), (7, 3));
+        // back up inside the window leaves the offset where it was
+        let mut steps = down.to_vec();
+        steps.extend([CursorMove::Up; 2]);
+        assert_eq!(navigate(10, 5, &steps), (5, 3));
+        // and past its top drags the window along
+        steps.extend([CursorMove::Up; 3]);
+        assert_eq!(navigate(10, 5, &steps), (2, 2));
+    }
+
+    #[test]
+    fn stops_at_either_end_instead_of_wrapping() {
+        assert_eq!(navigate(10, 5, &[CursorMove::Up]), (0, 0));
+        assert_eq!(navigate(10, 5, &[CursorMove::Down; 15]), (9, 5));
+        let mut steps = vec![CursorMove::Down; 15];
+        steps.push(CursorMove::Down);
+        assert_eq!(navigate(10, 5, &steps), (9, 5));
+    }
+
+    #[test]
+    fn list_shorter_than_the_viewport_never_scrolls() {
+        assert_eq!(navigate(3, 5, &[CursorMove::Down; 5]), (2, 0));
+        assert_eq!(navigate(0, 5, &[CursorMove::Down]), (0, 0));
+    }
 }
@@ -0,0 +1,58 @@
+use crate::progress::RunProgress;
+use dungeon_raid_core::game::Game;
+
+const OBJECTIVE_SPECIALS_TO_DEFEAT: u64 = 5;
+const OBJECTIVE_TURN_BUDGET: u64 = 150;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum GameMode {
+    // survive as long as possible; today's game
+    Endless,
+    // defeat a number of specials within a turn budget
+    Objective,
+}
+
+impl GameMode {
+    pub fn from_arg(arg: &str) -> Option<Self> {
+        match arg {
+            "endless" => Some(GameMode::Endless),
+            "objective" => Some(GameMode::Objective),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            GameMode::Endless => "endless",
+            GameMode::Objective => "objective",
+        }
+    }
+
+    pub fn objective_complete(&self, progress: &RunProgress) -> bool {
+        match self {
+            GameMode::Endless => false,
+            GameMode::Objective => progress.specials_slain >= OBJECTIVE_SPECIALS_TO_DEFEAT,
+        }
+    }
+
+    pub fn objective_failed(&self, progress: &RunProgress, game: &Game) -> bool {
+        match self {
+            GameMode::Endless => false,
+            GameMode::Objective => {
+                !self.objective_complete(progress) && game.turn() as u64 >= OBJECTIVE_TURN_BUDGET
+            }
+        }
+    }
+
+    pub fn objective_display(&self, progress: &RunProgress, game: &Game) -> Option<String> {
+        match self {
+            GameMode::Endless => None,
+            GameMode::Objective => Some(format!(
+                "objective: specials {}/{}, turns left {}",
+                progress.specials_slain.min(OBJECTIVE_SPECIALS_TO_DEFEAT),
+                OBJECTIVE_SPECIALS_TO_DEFEAT,
+                OBJECTIVE_TURN_BUDGET.saturating_sub(game.turn() as u64)
+            )),
+        }
+    }
+}
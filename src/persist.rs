@@ -0,0 +1,54 @@
+use crate::logging;
+use serde::{de::DeserializeOwned, Serialize};
+use std::sync::Mutex;
+
+// files that failed to parse this session; saving over them would lose the user's edits
+static UNPARSEABLE: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// loads `path` as toml, falling back to the default when it's missing or unreadable;
+/// a file that fails to parse is also remembered so `save_toml` leaves it alone
+pub fn load_toml<T: DeserializeOwned + Default>(path: &str) -> T {
+    match std::fs::read_to_string(path) {
+        Ok(contents) => match toml::from_str(&contents) {
+            Ok(value) => value,
+            Err(e) => {
+                logging::info(&format!("failed to parse {}: {}", path, e));
+                let mut unparseable = UNPARSEABLE.lock().unwrap();
+                if !unparseable.iter().any(|p| p == path) {
+                    unparseable.push(path.to_string());
+                }
+                T::default()
+            }
+        },
+        Err(_) => T::default(),
+    }
+}
+
+/// the files loaded so far that failed to parse, in the order they were loaded
+pub fn unparseable_files() -> Vec<String> {
+    UNPARSEABLE.lock().unwrap().clone()
+}
+
+/// writes to a temporary file and renames it over `path` so a crash never leaves it half written
+pub fn write_atomically(path: &str, contents: &str) -> Result<(), String> {
+    let tmp_path = format!("{}.tmp", path);
+    std::fs::write(&tmp_path, contents)
+        .and_then(|_| std::fs::rename(&tmp_path, path))
+        .map_err(|e| e.to_string())
+}
+
+pub fn save_toml<T: Serialize>(path: &str, value: &T) {
+    if UNPARSEABLE.lock().unwrap().iter().any(|p| p == path) {
+        logging::info(&format!(
+            "not saving {}, it failed to parse at startup and would be overwritten",
+            path
+        ));
+        return;
+    }
+    let result = toml::to_string(value)
+        .map_err(|e| e.to_string())
+        .and_then(|contents| write_atomically(path, &contents));
+    if let Err(e) = result {
+        logging::info(&format!("failed to save {}: {}", path, e));
+    }
+}
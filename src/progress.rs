@@ -0,0 +1,80 @@
+use dungeon_raid_core::game::{
+    tile::{TilePosition, TileType},
+    Game,
+};
+use serde::{Deserialize, Serialize};
+
+/// The core's running totals, taken before a drop so its summary can be measured against them.
+#[derive(Default, Clone, Copy)]
+pub struct StatTotals {
+    pub enemies_slain: u64,
+    pub coins_collected: u64,
+}
+
+impl StatTotals {
+    pub fn of(game: &Game) -> Self {
+        let stats = game.stats();
+        Self {
+            enemies_slain: stats.enemies_slain as u64,
+            coins_collected: stats.coins_collected as u64,
+        }
+    }
+}
+
+/// What a single drop accomplished; the counts come from the core's stats, only the specials
+/// are worked out by comparing the chain to the board after the drop.
+#[derive(Default, Clone, Copy)]
+pub struct TurnSummary {
+    pub chain_length: usize,
+    pub enemies_slain: u64,
+    pub specials_slain: u64,
+    pub coins_collected: u64,
+}
+
+impl TurnSummary {
+    /// `selected` is the chain as it was before `drop_selection` and `before` the totals from
+    /// then; call this before gravity runs
+    pub fn from_drop(
+        selected: &[(TilePosition, TileType)],
+        before: StatTotals,
+        game: &Game,
+    ) -> Self {
+        let after = StatTotals::of(game);
+        let specials_slain = selected
+            .iter()
+            .filter(|(tp, tile_type)| {
+                matches!(tile_type, TileType::Special)
+                    && !matches!(
+                        game.get_tile(tp).map(|t| t.tile_type),
+                        Some(TileType::Enemy | TileType::Special)
+                    )
+            })
+            .count() as u64;
+        Self {
+            chain_length: selected.len(),
+            enemies_slain: after.enemies_slain.saturating_sub(before.enemies_slain),
+            specials_slain,
+            coins_collected: after.coins_collected.saturating_sub(before.coins_collected),
+        }
+    }
+}
+
+/// Aggregates for the current run that the core doesn't track on its own; saved with the game.
+/// Turns, enemies slain and coins come from `game.turn()` and `game.stats()` instead.
+#[derive(Default, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RunProgress {
+    pub longest_chain: usize,
+    pub specials_slain: u64,
+    pub game_over: bool,
+    pub victory: bool,
+}
+
+impl RunProgress {
+    pub fn record_turn(&mut self, summary: &TurnSummary) {
+        if summary.chain_length > self.longest_chain {
+            self.longest_chain = summary.chain_length;
+        }
+        self.specials_slain += summary.specials_slain;
+    }
+}
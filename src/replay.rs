@@ -0,0 +1,232 @@
+use crate::{
+    difficulty_from_name, difficulty_name, gravity_from_name, gravity_name,
+    keybindings::{key_from_name, key_name, Keybindings},
+    mode::GameMode,
+    persist::write_atomically,
+    settings::ImprovementCommit,
+};
+use crossterm::event::KeyCode;
+use dungeon_raid_core::game::{Difficulty, Game, GravityDirection};
+use std::{collections::BTreeMap, time::Duration};
+
+// header lines, in the order they're written; each one is required
+const HEADERS: [&str; 9] = [
+    "seed",
+    "mode",
+    "difficulty",
+    "gravity",
+    "improvement-commit",
+    "four-way-selection",
+    "min-selection-length",
+    "big-swing-threshold",
+    "debug",
+];
+
+/// A recorded run: what it started from and every key pressed, in order.
+///
+/// Stored as plain text so a replay can be trimmed or edited by hand:
+///
+/// ```text
+/// seed 1234
+/// mode endless
+/// difficulty normal
+/// gravity down
+/// improvement-commit enter-with-preview
+/// four-way-selection false
+/// min-selection-length default
+/// big-swing-threshold off
+/// debug false
+/// bind move_up k Up
+/// bind drop Space
+/// x 0
+/// l 310
+/// Space 95
+/// check 1 9f2c04be41d7a3e5
+/// ```
+///
+/// The header pins everything that changes what a key does or how the board plays,
+/// including the keys.toml bindings in force. Each key is followed by the milliseconds
+/// since the previous one, which only matters for double presses. A `check` line holds
+/// the turn and a digest of the game as it stood once the keys above it had played out,
+/// for `--verify-replay`.
+pub struct Replay {
+    pub seed: u64,
+    pub mode: GameMode,
+    pub difficulty: Difficulty,
+    pub gravity: GravityDirection,
+    // changes what Enter does on the improvement screen, so it's pinned to the recording's
+    pub improvement_commit: ImprovementCommit,
+    pub four_way_selection: bool,
+    pub min_selection_length: Option<usize>,
+    pub big_swing_threshold: Option<u32>,
+    // --debug steps combat one key at a time
+    pub debug: bool,
+    pub keybindings: Keybindings,
+    pub keys: Vec<(KeyCode, Duration)>,
+    pub checks: Vec<Check>,
+}
+
+/// The game as recorded after the first `after_keys` keys.
+pub struct Check {
+    pub after_keys: usize,
+    pub turn: u64,
+    pub digest: u64,
+}
+
+impl Check {
+    pub fn new(after_keys: usize, game: &Game) -> Self {
+        Self {
+            after_keys,
+            turn: game.turn() as u64,
+            digest: digest(game),
+        }
+    }
+
+    /// whether `game` is where the recording had it
+    pub fn matches(&self, game: &Game) -> bool {
+        game.turn() as u64 == self.turn && digest(game) == self.digest
+    }
+}
+
+// FNV-1a over the core's json, which covers the board, the player, the specials and the rng
+fn digest(game: &Game) -> u64 {
+    game.to_json()
+        .unwrap_or_default()
+        .bytes()
+        .fold(0xcbf29ce484222325, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        })
+}
+
+impl Replay {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let contents =
+            std::fs::read_to_string(path).map_err(|e| format!("failed to read {}: {}", path, e))?;
+        let mut header: BTreeMap<&str, &str> = BTreeMap::new();
+        let mut bindings: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        let mut keys = vec![];
+        let mut checks = vec![];
+        for (idx, line) in contents.lines().enumerate() {
+            if line.is_empty() {
+                continue;
+            }
+            let (word, rest) = line.split_once(' ').unwrap_or((line, ""));
+            if keys.is_empty() && word == "bind" {
+                let mut names = rest.split(' ').filter(|name| !name.is_empty());
+                let action = names.next().ok_or(format!(
+                    "{} line {}: 'bind' needs an action",
+                    path,
+                    idx + 1
+                ))?;
+                bindings.insert(action.to_string(), names.map(String::from).collect());
+            } else if keys.is_empty() && HEADERS.contains(&word) {
+                header.insert(word, rest.trim());
+            } else if word == "check" {
+                let (turn, digest) = rest
+                    .split_once(' ')
+                    .and_then(|(turn, digest)| {
+                        Some((turn.parse().ok()?, u64::from_str_radix(digest, 16).ok()?))
+                    })
+                    .ok_or(format!(
+                        "{} line {}: expected 'check <turn> <digest>'",
+                        path,
+                        idx + 1
+                    ))?;
+                checks.push(Check {
+                    after_keys: keys.len(),
+                    turn,
+                    digest,
+                });
+            } else {
+                keys.push(parse_key_line(line).ok_or(format!(
+                    "{} line {}: can't read '{}'",
+                    path,
+                    idx + 1,
+                    line
+                ))?);
+            }
+        }
+        let field = |name: &str| {
+            header
+                .get(name)
+                .copied()
+                .ok_or(format!("{} has no '{}' line", path, name))
+        };
+        let bad = |name: &str| format!("{}: can't read the '{}' line", path, name);
+        Ok(Self {
+            seed: field("seed")?.parse().map_err(|_| bad("seed"))?,
+            mode: GameMode::from_arg(field("mode")?).ok_or(bad("mode"))?,
+            difficulty: difficulty_from_name(field("difficulty")?).ok_or(bad("difficulty"))?,
+            gravity: gravity_from_name(field("gravity")?).ok_or(bad("gravity"))?,
+            improvement_commit: ImprovementCommit::from_name(field("improvement-commit")?)
+                .ok_or(bad("improvement-commit"))?,
+            four_way_selection: field("four-way-selection")?
+                .parse()
+                .map_err(|_| bad("four-way-selection"))?,
+            min_selection_length: parse_optional(field("min-selection-length")?, "default")
+                .ok_or(bad("min-selection-length"))?,
+            big_swing_threshold: parse_optional(field("big-swing-threshold")?, "off")
+                .ok_or(bad("big-swing-threshold"))?,
+            debug: field("debug")?.parse().map_err(|_| bad("debug"))?,
+            keybindings: Keybindings::from_table(path, bindings)?,
+            keys,
+            checks,
+        })
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let optional = |value: Option<String>, unset: &str| value.unwrap_or(unset.to_string());
+        let mut contents = format!(
+            "seed {}\nmode {}\ndifficulty {}\ngravity {}\nimprovement-commit {}\n\
+             four-way-selection {}\nmin-selection-length {}\nbig-swing-threshold {}\n\
+             debug {}\n",
+            self.seed,
+            self.mode.name(),
+            difficulty_name(self.difficulty),
+            gravity_name(self.gravity),
+            self.improvement_commit.name(),
+            self.four_way_selection,
+            optional(self.min_selection_length.map(|n| n.to_string()), "default"),
+            optional(self.big_swing_threshold.map(|n| n.to_string()), "off"),
+            self.debug,
+        );
+        for (action, keys) in self.keybindings.bindings() {
+            contents += &format!("bind {}", action.name());
+            for name in keys.iter().filter_map(|key| key_name(*key)) {
+                contents += &format!(" {}", name);
+            }
+            contents += "\n";
+        }
+        let mut checks = self.checks.iter().peekable();
+        for (idx, (key, gap)) in self.keys.iter().enumerate() {
+            while let Some(check) = checks.next_if(|check| check.after_keys <= idx) {
+                contents += &format!("check {} {:016x}\n", check.turn, check.digest);
+            }
+            if let Some(name) = key_name(*key) {
+                contents += &format!("{} {}\n", name, gap.as_millis());
+            }
+        }
+        for check in checks {
+            contents += &format!("check {} {:016x}\n", check.turn, check.digest);
+        }
+        write_atomically(path, &contents)
+    }
+}
+
+// a number, or `unset` for none
+fn parse_optional<T: std::str::FromStr>(value: &str, unset: &str) -> Option<Option<T>> {
+    if value == unset {
+        Some(None)
+    } else {
+        value.parse().ok().map(Some)
+    }
+}
+
+fn parse_key_line(line: &str) -> Option<(KeyCode, Duration)> {
+    // split from the right, the key itself may be a space-free symbol like ':'
+    let (name, gap) = line.rsplit_once(' ')?;
+    Some((
+        key_from_name(name)?,
+        Duration::from_millis(gap.parse().ok()?),
+    ))
+}
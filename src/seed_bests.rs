@@ -0,0 +1,48 @@
+use crate::persist::{load_toml, save_toml};
+use serde::{Deserialize, Serialize};
+
+const SEED_BESTS_FILE: &'static str = "seed_bests.toml";
+// the least recently played seeds are dropped past this many
+const MAX_SEEDS: usize = 200;
+
+/// The best score on each recently finished seed, so a retry with --seed has something to beat.
+/// Runs are only compared when everything that shapes them matches, see `App::seed_best_key`.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SeedBests {
+    // least recently played first
+    pub seeds: Vec<SeedBest>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SeedBest {
+    // the seed along with the run's settings; a string also since toml integers stop at i64::MAX
+    pub key: String,
+    pub score: u64,
+}
+
+impl SeedBests {
+    pub fn load() -> Self {
+        load_toml(SEED_BESTS_FILE)
+    }
+
+    pub fn best(&self, key: &str) -> Option<u64> {
+        self.seeds
+            .iter()
+            .find(|entry| entry.key == key)
+            .map(|entry| entry.score)
+    }
+
+    /// keeps the higher of `score` and the seed's best, marking the seed as just played
+    pub fn record(&mut self, key: String, score: u64) {
+        let best = match self.seeds.iter().position(|entry| entry.key == key) {
+            Some(idx) => self.seeds.remove(idx).score.max(score),
+            None => score,
+        };
+        self.seeds.push(SeedBest { key, score: best });
+        if self.seeds.len() > MAX_SEEDS {
+            self.seeds.drain(..self.seeds.len() - MAX_SEEDS);
+        }
+        save_toml(SEED_BESTS_FILE, self);
+    }
+}
@@ -0,0 +1,257 @@
+use crate::{
+    logging::LogSettings,
+    persist::{load_toml, save_toml},
+};
+use serde::{Deserialize, Serialize};
+
+const SETTINGS_FILE: &'static str = "settings.toml";
+
+#[derive(Copy, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HudPosition {
+    // beside the board when the terminal is too short for it below
+    #[default]
+    Auto,
+    Bottom,
+    Side,
+}
+
+impl HudPosition {
+    pub fn next(&self) -> Self {
+        match self {
+            HudPosition::Auto => HudPosition::Bottom,
+            HudPosition::Bottom => HudPosition::Side,
+            HudPosition::Side => HudPosition::Auto,
+        }
+    }
+}
+
+/// How the screen is arranged; restored on the next launch.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LayoutSettings {
+    pub show_run_info: bool,
+    pub hud_position: HudPosition,
+    // row and column numbers around the board
+    pub show_guide_labels: bool,
+    // two columns per blot, for terminals that draw theme.toml's emoji blots double-width;
+    // unset means on whenever a blot is one
+    pub wide_glyphs: Option<bool>,
+}
+
+impl Default for LayoutSettings {
+    fn default() -> Self {
+        Self {
+            show_run_info: true,
+            hud_position: HudPosition::Auto,
+            show_guide_labels: false,
+            wide_glyphs: None,
+        }
+    }
+}
+
+/// How large HUD values like coins and XP are written.
+#[derive(Copy, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NumberFormat {
+    // 12345
+    #[default]
+    Raw,
+    // 12,345
+    Separators,
+    // 12.3k
+    Compact,
+}
+
+impl NumberFormat {
+    /// always uses ',' and '.' regardless of locale
+    pub fn format(&self, value: u64) -> String {
+        match self {
+            NumberFormat::Raw => value.to_string(),
+            NumberFormat::Separators => {
+                let digits = value.to_string();
+                let mut formatted = String::new();
+                for (idx, c) in digits.chars().enumerate() {
+                    if idx > 0 && (digits.len() - idx) % 3 == 0 {
+                        formatted.push(',');
+                    }
+                    formatted.push(c);
+                }
+                formatted
+            }
+            NumberFormat::Compact => {
+                if value < 1_000 {
+                    return value.to_string();
+                }
+                // rounds to a tenth before picking the unit, so 999_950 is "1.0M" and not "1000.0k"
+                let suffixes = [(1_000, 'k'), (1_000_000, 'M'), (1_000_000_000, 'B')];
+                let mut compact = String::new();
+                for (magnitude, suffix) in suffixes {
+                    let tenths = value.saturating_add(magnitude / 20) / (magnitude / 10);
+                    compact = format!("{}.{}{}", tenths / 10, tenths % 10, suffix);
+                    if tenths < 10_000 {
+                        break;
+                    }
+                }
+                compact
+            }
+        }
+    }
+}
+
+/// How hit points and shields are written.
+#[derive(Copy, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StatDisplay {
+    // 12/20
+    #[default]
+    Fraction,
+    // 60%
+    Percentage,
+    // 12/20 (60%)
+    Both,
+}
+
+impl StatDisplay {
+    pub fn format(&self, current: u64, max: u64) -> String {
+        let percentage = if max == 0 { 0 } else { current * 100 / max };
+        match self {
+            StatDisplay::Fraction => format!("{}/{}", current, max),
+            StatDisplay::Percentage => format!("{}%", percentage),
+            StatDisplay::Both => format!("{}/{} ({}%)", current, max, percentage),
+        }
+    }
+}
+
+/// How the tile a selection starts from is marked on the board.
+#[derive(Copy, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SelectionStartStyle {
+    #[default]
+    Blink,
+    // attacking enemies are drawn reversed already, so a start on one gets '@' instead
+    Reversed,
+    // replaces the blot with '@'
+    Glyph,
+}
+
+/// How the selection path between tiles is drawn.
+#[derive(Copy, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PathStyle {
+    // / \ | - with X where diagonals cross
+    #[default]
+    Ascii,
+    // arrows pointing along the chain, for terminals with good unicode fonts
+    Unicode,
+}
+
+/// When the chosen improvements are handed to the core, which can't be undone.
+#[derive(Copy, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ImprovementCommit {
+    // as soon as enough choices are toggled
+    Instant,
+    // Enter once enough choices are toggled
+    SpaceThenEnter,
+    // Enter to review the preview, Enter again to commit
+    #[default]
+    EnterWithPreview,
+}
+
+impl ImprovementCommit {
+    /// same spelling as in settings.toml
+    pub fn name(&self) -> &'static str {
+        match self {
+            ImprovementCommit::Instant => "instant",
+            ImprovementCommit::SpaceThenEnter => "space-then-enter",
+            ImprovementCommit::EnterWithPreview => "enter-with-preview",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "instant" => Some(ImprovementCommit::Instant),
+            "space-then-enter" => Some(ImprovementCommit::SpaceThenEnter),
+            "enter-with-preview" => Some(ImprovementCommit::EnterWithPreview),
+            _ => None,
+        }
+    }
+}
+
+/// Which notification severities stay quiet: no bell and no flash, only the status line.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotificationSettings {
+    pub mute_info: bool,
+    pub mute_warning: bool,
+    pub mute_danger: bool,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub layout: LayoutSettings,
+    pub number_format: NumberFormat,
+    pub stat_display: StatDisplay,
+    pub selection_start_style: SelectionStartStyle,
+    pub path_style: PathStyle,
+    pub improvement_commit: ImprovementCommit,
+    pub notifications: NotificationSettings,
+    // a drop that would change HP by at least this much asks for a second Space
+    pub big_swing_threshold: Option<u32>,
+    // the shortest chain that can be dropped, the core's 3 when unset; 1 allows any drop
+    pub min_selection_length: Option<usize>,
+    // chains only go up, down, left and right, for a harder game
+    pub four_way_selection: bool,
+    // no blinking or animations
+    pub reduced_motion: bool,
+    // tiles fall a row at a time after a drop; off by default as slow terminals flicker
+    pub animate_gravity: bool,
+    // gentle tips in the status line, off unless asked for
+    pub coaching: bool,
+    pub log: LogSettings,
+}
+
+impl Settings {
+    pub fn load() -> Self {
+        load_toml(SETTINGS_FILE)
+    }
+
+    pub fn save(&self) {
+        save_toml(SETTINGS_FILE, self);
+    }
+
+    pub fn effective_selection_start_style(&self) -> SelectionStartStyle {
+        match self.selection_start_style {
+            SelectionStartStyle::Blink if self.reduced_motion => SelectionStartStyle::Reversed,
+            style => style,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NumberFormat;
+
+    #[test]
+    fn compact_rounds_before_choosing_the_unit() {
+        let cases = [
+            (0, "0"),
+            (999, "999"),
+            (1_000, "1.0k"),
+            (1_049, "1.0k"),
+            (1_050, "1.1k"),
+            (999_949, "999.9k"),
+            (999_950, "1.0M"),
+            (1_000_000, "1.0M"),
+            (999_949_999, "999.9M"),
+            (999_950_000, "1.0B"),
+            (999_999_950, "1.0B"),
+            (1_000_000_000_000, "1000.0B"),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(NumberFormat::Compact.format(value), expected, "{}", value);
+        }
+    }
+}
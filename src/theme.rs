@@ -0,0 +1,84 @@
+use crate::persist::load_toml;
+use dungeon_raid_core::game::tile::TileType;
+use ratatui::style::Color;
+use serde::{Deserialize, Serialize};
+use unicode_width::UnicodeWidthChar;
+
+const THEME_FILE: &'static str = "theme.toml";
+
+/// One optional value per tile type; a missing one keeps the built-in look.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PerTileType<T> {
+    pub potion: Option<T>,
+    pub shield: Option<T>,
+    pub coin: Option<T>,
+    pub sword: Option<T>,
+    pub enemy: Option<T>,
+    pub special: Option<T>,
+}
+
+impl<T> Default for PerTileType<T> {
+    fn default() -> Self {
+        Self {
+            potion: None,
+            shield: None,
+            coin: None,
+            sword: None,
+            enemy: None,
+            special: None,
+        }
+    }
+}
+
+impl<T: Copy> PerTileType<T> {
+    pub fn get(&self, tile_type: TileType) -> Option<T> {
+        match tile_type {
+            TileType::Potion => self.potion,
+            TileType::Shield => self.shield,
+            TileType::Coin => self.coin,
+            TileType::Sword => self.sword,
+            TileType::Enemy => self.enemy,
+            TileType::Special => self.special,
+            _ => None,
+        }
+    }
+}
+
+/// Tile colors and blot chars read from theme.toml at startup, e.g.
+///
+/// ```toml
+/// [light]
+/// potion = ["Magenta", "White"] # bg, fg
+/// [blots]
+/// enemy = "X"
+/// ```
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Theme {
+    pub dark: PerTileType<(Color, Color)>,
+    pub light: PerTileType<(Color, Color)>,
+    pub blots: PerTileType<char>,
+}
+
+impl Theme {
+    pub fn load() -> Self {
+        load_toml(THEME_FILE)
+    }
+
+    /// whether any blot is an emoji or another char terminals draw two columns wide
+    pub fn has_wide_blots(&self) -> bool {
+        let blots = &self.blots;
+        [
+            blots.potion,
+            blots.shield,
+            blots.coin,
+            blots.sword,
+            blots.enemy,
+            blots.special,
+        ]
+        .into_iter()
+        .flatten()
+        .any(|blot| blot.width() == Some(2))
+    }
+}
@@ -0,0 +1,30 @@
+// shared by the integration tests and the unit tests in src/main.rs, so it only uses the core
+use dungeon_raid_core::game::Game;
+
+/// one full turn through the core alone, the way a bot would play it:
+/// select_tile* -> drop_selection -> apply_incoming_damage
+///     -> apply_gravity_and_randomize_new_tiles -> run_end_of_turn_on_specials
+///
+/// takes the first improvements offered and the longest legal chain; false, with the turn
+/// left as it was, when the chain couldn't be dropped
+pub fn play_turn(game: &mut Game) -> bool {
+    if let Some(num_to_choose) = game.improvement_choice_set().map(|set| set.num_to_choose) {
+        game.choose_improvements(&(0..num_to_choose).collect::<Vec<usize>>());
+    }
+    if let Some(chain) = game
+        .legal_selections()
+        .into_iter()
+        .max_by_key(|chain| chain.len())
+    {
+        for tp in chain.iter() {
+            game.select_tile(tp);
+        }
+    }
+    if !game.drop_selection() {
+        return false;
+    }
+    game.apply_incoming_damage();
+    game.apply_gravity_and_randomize_new_tiles();
+    game.run_end_of_turn_on_specials();
+    true
+}
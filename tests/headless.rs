@@ -0,0 +1,21 @@
+mod common;
+
+use common::play_turn;
+use dungeon_raid_core::game::Game;
+
+#[test]
+fn plays_a_hundred_turns_without_a_terminal() {
+    let mut seed = 1234;
+    let mut game = Game::with_seed(seed);
+    for _ in 0..100 {
+        // a lost run just moves on to the next seed, the turns still count
+        if game.is_game_over() {
+            seed += 1;
+            game = Game::with_seed(seed);
+        }
+        let board = game.board();
+        assert_eq!(board.len(), game.board_height());
+        assert!(board.iter().all(|row| row.len() == game.board_width()));
+        assert!(play_turn(&mut game), "no legal chain could be dropped");
+    }
+}
@@ -0,0 +1,33 @@
+use std::{path::Path, process::Command};
+
+// every recording under tests/replays has to play out exactly as it did when it was made
+#[test]
+fn recorded_replays_still_play_out_the_same() {
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/replays");
+    // keeps log.txt and settings.toml lookups out of the checkout
+    let scratch = std::env::temp_dir().join("tui-dungeon-raid-replays");
+    std::fs::create_dir_all(&scratch).expect("failed to create scratch dir");
+    let mut entries: Vec<_> = std::fs::read_dir(&fixtures)
+        .expect("failed to read tests/replays")
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().map_or(false, |ext| ext == "replay"))
+        .collect();
+    entries.sort();
+    assert!(
+        !entries.is_empty(),
+        "no recordings in tests/replays, see its README.md for making one"
+    );
+    for path in entries {
+        let output = Command::new(env!("CARGO_BIN_EXE_tui-dungeon-raid"))
+            .arg("--verify-replay")
+            .arg(&path)
+            .current_dir(&scratch)
+            .output()
+            .expect("failed to run tui-dungeon-raid");
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+}